@@ -56,26 +56,34 @@ class VersionReader {
     });
   }
 
-  readStream() {
+  readStream(opts) {
     const self = this;
-    const buf = Buffer.alloc(8 * 1024, 0);
+    const chunkSize = (opts && opts.chunkSize) || 8 * 1024;
 
-    return Promise.resolve(
-      new Readable({
-        read() {
-          try {
-            const read = self.inner.read(buf.buffer, 0, buf.length);
-            if (read === 0) {
-              this.push(null);
-            } else {
-              this.push(buf.slice(0, read));
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureChunkSize(chunkSize);
+        resolve(
+          new Readable({
+            highWaterMark: chunkSize,
+            read() {
+              try {
+                const chunk = self.inner.readChunk(chunkSize);
+                if (chunk.byteLength === 0) {
+                  this.push(null);
+                } else {
+                  this.push(Buffer.from(chunk));
+                }
+              } catch (err) {
+                process.nextTick(() => this.emit('error', err));
+              }
             }
-          } catch (err) {
-            process.nextTick(() => this.emit('error', err));
-          }
-        }
-      })
-    );
+          })
+        );
+      } catch (err) {
+        reject(err);
+      }
+    });
   }
 
   seek(arg) {
@@ -92,11 +100,43 @@ class VersionReader {
       }
     });
   }
+
+  version() {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        const ver = self.inner.version();
+        resolve(ver);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  hash(algo = 'sha256') {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(algo);
+        self.inner.hash(algo, (err, hash) => {
+          if (err) {
+            reject(err);
+          } else {
+            resolve(hash);
+          }
+        });
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
 }
 
 class File {
-  constructor(ptr) {
+  constructor(ptr, repo, path) {
     this.inner = new zbox.File(ptr);
+    this.repo = repo;
+    this.path = path;
   }
 
   close() {
@@ -183,11 +223,16 @@ class File {
     });
   }
 
-  finish() {
+  finish(note) {
     const self = this;
     return new Promise((resolve, reject) => {
       try {
+        if (note !== undefined) utils.ensureObject(note);
         self.inner.finish();
+        if (note) {
+          const ver = self.inner.currVersion();
+          self.repo.annotateVersion(self.path, ver, note);
+        }
         resolve();
       } catch (err) {
         reject(err);
@@ -259,6 +304,24 @@ class File {
     });
   }
 
+  hash(algo = 'sha256') {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(algo);
+        self.inner.hash(algo, (err, hash) => {
+          if (err) {
+            reject(err);
+          } else {
+            resolve(hash);
+          }
+        });
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
   currVersion() {
     const self = this;
     return new Promise((resolve, reject) => {
@@ -300,7 +363,7 @@ class File {
     const self = this;
     return new Promise((resolve, reject) => {
       try {
-        const hist = self.inner.history();
+        const hist = self.inner.history(self.repo, self.path);
         resolve(hist);
       } catch (err) {
         reject(err);
@@ -309,6 +372,65 @@ class File {
   }
 }
 
+class Snapshot {
+  constructor(repo, manifest) {
+    this.repo = repo;
+    this.name = manifest.name;
+    this.createdAt = manifest.createdAt;
+    this.dirs = manifest.dirs;
+    this.files = new Map(manifest.files.map(f => [f.path, f.version]));
+  }
+
+  paths() {
+    return Promise.resolve(this.dirs.concat(Array.from(this.files.keys())));
+  }
+
+  readDir(path) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(path);
+        if (path !== '/' && !self.dirs.includes(path)) {
+          throw new Error(`Path not found in snapshot: ${path}`);
+        }
+        const prefix = path.endsWith('/') ? path : `${path}/`;
+        const isChild = p => p.startsWith(prefix) &&
+          !p.slice(prefix.length).includes('/');
+        const dirs = self.dirs.filter(isChild).map(p => ({
+          path: p,
+          fileName: p.slice(prefix.length),
+          isDir: true
+        }));
+        const files = Array.from(self.files.keys()).filter(isChild).map(p => ({
+          path: p,
+          fileName: p.slice(prefix.length),
+          isDir: false,
+          version: self.files.get(p)
+        }));
+        resolve(dirs.concat(files));
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  openFile(path) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(path);
+        if (!self.files.has(path)) {
+          throw new Error(`File not found in snapshot: ${path}`);
+        }
+        const ptr = self.repo.openVersion(path, self.files.get(path));
+        resolve(new VersionReader(ptr));
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+}
+
 class Repo {
   constructor(ptr) {
     this.inner = new zbox.Repo(ptr);
@@ -343,17 +465,24 @@ class Repo {
     return new Promise((resolve, reject) => {
       try {
         utils.ensureObject(arg);
-        utils.ensureString2(arg.oldPwd, arg.newPwd);
+        utils.ensurePassword(arg.oldPwd, arg.oldKeyFile);
+        utils.ensurePassword(arg.newPwd, arg.newKeyFile);
         utils.ensureNumber2(arg.opsLimit, arg.memLimit);
         self.inner.resetPassword(
           arg.oldPwd,
           arg.newPwd,
           arg.opsLimit,
-          arg.memLimit
+          arg.memLimit,
+          arg.oldKeyFile,
+          arg.newKeyFile
         );
         resolve();
       } catch (err) {
         reject(err);
+      } finally {
+        if (utils.isObject(arg) && arg.wipePassword) {
+          utils.wipePassword(arg.oldPwd, arg.newPwd);
+        }
       }
     });
   }
@@ -403,7 +532,7 @@ class Repo {
       try {
         utils.ensureString(path);
         const ptr = self.inner.createFile(path);
-        resolve(new File(ptr));
+        resolve(new File(ptr, self.inner, path));
       } catch (err) {
         reject(err);
       }
@@ -430,7 +559,7 @@ class Repo {
         }
 
         const ptr = self.inner.openFile(path, opts);
-        resolve(new File(ptr));
+        resolve(new File(ptr, self.inner, path));
       } catch (err) {
         reject(err);
       }
@@ -463,19 +592,44 @@ class Repo {
     });
   }
 
-  readDir(path) {
+  readDir(path, opts) {
     const self = this;
     return new Promise((resolve, reject) => {
       try {
         utils.ensureString(path);
-        const dirs = self.inner.readDir(path);
-        resolve(dirs);
+        if (opts === undefined) {
+          resolve(self.inner.readDir(path));
+          return;
+        }
+        utils.ensureObject(opts);
+        const page = self.inner.readDirPage(path, opts);
+        resolve(page);
       } catch (err) {
         reject(err);
       }
     });
   }
 
+  async *walk(path, opts = {}) {
+    utils.ensureString(path);
+    utils.ensureObject(opts);
+    if (opts.followOrder !== undefined) {
+      utils.ensureString(opts.followOrder);
+    }
+    const walker = new zbox.Walker(this.inner, path, opts);
+    try {
+      for (;;) {
+        const ent = walker.next();
+        if (ent === undefined) {
+          return;
+        }
+        yield ent;
+      }
+    } finally {
+      walker.close();
+    }
+  }
+
   metadata(path) {
     const self = this;
     return new Promise((resolve, reject) => {
@@ -502,6 +656,415 @@ class Repo {
     });
   }
 
+  openVersion(path, verNum) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(path);
+        utils.ensureNumber(verNum);
+        const ptr = self.inner.openVersion(path, verNum);
+        resolve(new VersionReader(ptr));
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  openVersionAt(path, time) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(path);
+        const secs = utils.ensureTime(time);
+        const ptr = self.inner.openVersionAt(path, secs);
+        resolve(new VersionReader(ptr));
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  diffVersions(path, verA, verB) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(path);
+        utils.ensureNumber2(verA, verB);
+        const diff = self.inner.diffVersions(path, verA, verB);
+        resolve(diff);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  // Kept versions are renumbered from 1 and get new creation time, the
+  // report maps old version numbers to new ones for each pruned file.
+  pruneHistory(path, policy) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(path);
+        const opts = utils.ensurePrunePolicy(policy);
+        const report = self.inner.pruneHistory(path, opts);
+        resolve(report);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  pruneAllHistory(policy) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        const opts = utils.ensurePrunePolicy(policy);
+        const report = self.inner.pruneAllHistory(opts);
+        resolve(report);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  labelVersion(path, verNum, label) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString2(path, label);
+        utils.ensureNumber(verNum);
+        self.inner.labelVersion(path, verNum, label);
+        resolve();
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  createSnapshot(name) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(name);
+        const info = self.inner.createSnapshot(name);
+        resolve(info);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  listSnapshots() {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        const list = self.inner.listSnapshots();
+        resolve(list);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  openSnapshot(name) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(name);
+        const manifest = self.inner.openSnapshot(name);
+        resolve(new Snapshot(self.inner, manifest));
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  restoreSnapshot(name) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(name);
+        const report = self.inner.restoreSnapshot(name);
+        resolve(report);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  glob(pattern, opts = {}) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(pattern);
+        utils.ensureObject(opts);
+        const ignore = opts.ignore || [];
+        utils.ensureStringArray(ignore);
+        const ents = self.inner.glob(pattern, ignore);
+        resolve(ents);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  removeGlob(pattern, opts = {}) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(pattern);
+        utils.ensureObject(opts);
+        const ignore = opts.ignore || [];
+        utils.ensureStringArray(ignore);
+        const paths = self.inner.removeGlob(pattern, ignore);
+        resolve(paths);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  du(path, opts = {}) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(path);
+        utils.ensureObject(opts);
+        const top = opts.top === undefined ? 10 : opts.top;
+        utils.ensureNumber(top);
+        const usage = self.inner.du(path, top);
+        resolve(usage);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  changesSince(time, opts = {}) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        const secs = utils.ensureTime(time);
+        utils.ensureObject(opts);
+        const path = opts.path || '/';
+        utils.ensureString(path);
+        const changes = self.inner.changesSince(secs, path);
+        resolve(changes);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  copyDirAll(from, to, opts = {}) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString2(from, to);
+        utils.ensureObject(opts);
+        utils.ensureOptionalFunction(opts.onProgress);
+        const copied = self.inner.copyDirAll(from, to, opts, opts.onProgress);
+        resolve({ copied });
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  move(from, to, opts = {}) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString2(from, to);
+        utils.ensureObject(opts);
+        utils.ensureOptionalFunction(opts.onProgress);
+        const overwrite = !!opts.overwrite;
+        const moved = self.inner.move(from, to, overwrite, opts.onProgress);
+        resolve({ moved });
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  importFrom(hostPath, repoPath, opts = {}) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString2(hostPath, repoPath);
+        utils.ensureObject(opts);
+        utils.ensureOptionalFunction(opts.filter);
+        utils.ensureOptionalFunction(opts.onProgress);
+        const filter =
+          opts.filter && ((path, isDir) => !!opts.filter(path, isDir));
+        const report = self.inner.importFrom(
+          hostPath,
+          repoPath,
+          !!opts.overwrite,
+          filter,
+          opts.onProgress
+        );
+        resolve(report);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  exportTo(repoPath, hostPath, opts = {}) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString2(repoPath, hostPath);
+        utils.ensureObject(opts);
+        utils.ensureOptionalFunction(opts.onProgress);
+        const version = opts.version === undefined ? 'latest' : opts.version;
+        const time =
+          version === 'latest' ? undefined : utils.ensureTime(version);
+        const report = self.inner.exportTo(
+          repoPath,
+          hostPath,
+          !!opts.overwrite,
+          time,
+          opts.onProgress
+        );
+        resolve(report);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  exportTar(path, opts) {
+    const self = this;
+    const chunkSize = (opts && opts.chunkSize) || 64 * 1024;
+
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(path);
+        utils.ensureChunkSize(chunkSize);
+        const tar = new zbox.TarReader(self.inner, path);
+        resolve(
+          new Readable({
+            highWaterMark: chunkSize,
+            read() {
+              try {
+                const chunk = tar.readChunk(chunkSize);
+                if (chunk.byteLength === 0) {
+                  tar.close();
+                  this.push(null);
+                } else {
+                  this.push(Buffer.from(chunk));
+                }
+              } catch (err) {
+                tar.close();
+                process.nextTick(() => this.emit('error', err));
+              }
+            }
+          })
+        );
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  async importTar(readable, destPath) {
+    utils.ensureObject(readable);
+    utils.ensureString(destPath);
+    const tar = new zbox.TarWriter(this.inner, destPath);
+    try {
+      for await (const chunk of readable) {
+        const buf = utils.ensureBufType(chunk);
+        tar.write(buf.ab, buf.offset, buf.len);
+      }
+      return tar.finish();
+    } finally {
+      tar.close();
+    }
+  }
+
+  verify(opts = {}) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureObject(opts);
+        const path = opts.path || '/';
+        utils.ensureString(path);
+        utils.ensureOptionalFunction(opts.onProgress);
+        const report = self.inner.verify(
+          path,
+          !!opts.allVersions,
+          opts.onProgress
+        );
+        resolve(report);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  hashFile(path, algo = 'sha256') {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString2(path, algo);
+        self.inner.hashFile(path, algo, (err, hash) => {
+          if (err) {
+            reject(err);
+          } else {
+            resolve(hash);
+          }
+        });
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  exportZip(path, opts = {}) {
+    const self = this;
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureString(path);
+        utils.ensureObject(opts);
+        if (opts.compressionLevel !== undefined) {
+          utils.ensureNumber(opts.compressionLevel);
+        }
+        if (opts.password !== undefined) utils.ensureString(opts.password);
+        const buf = self.inner.exportZip(path, opts);
+        resolve(Buffer.from(buf));
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  async importZip(source, destPath, opts = {}) {
+    utils.ensureString(destPath);
+    utils.ensureObject(opts);
+    if (opts.password !== undefined) utils.ensureString(opts.password);
+
+    // zip central directory is at the end, so the whole archive is needed
+    let data = source;
+    if (!utils.isBufType(source)) {
+      utils.ensureObject(source);
+      const chunks = [];
+      for await (const chunk of source) {
+        chunks.push(Buffer.from(chunk));
+      }
+      data = Buffer.concat(chunks);
+    }
+    const buf = utils.ensureBufType(data);
+    return this.inner.importZip(
+      buf.ab,
+      buf.offset,
+      buf.len,
+      destPath,
+      opts.password
+    );
+  }
+
   copy(arg) {
     const self = this;
     return new Promise((resolve, reject) => {
@@ -673,14 +1236,20 @@ module.exports = class Zbox {
     return new Promise((resolve, reject) => {
       try {
         utils.ensureObject(args);
-        utils.ensureString2(args.uri, args.pwd);
+        utils.ensureString(args.uri);
         args.opts = args.opts || {};
         utils.ensureObject(args.opts);
+        utils.ensurePassword(args.pwd, args.opts.keyFile);
 
         const ptr = zbox.openRepo(args);
         resolve(new Repo(ptr));
       } catch (err) {
         reject(err);
+      } finally {
+        if (utils.isObject(args) && utils.isObject(args.opts) &&
+            args.opts.wipePassword) {
+          utils.wipePassword(args.pwd);
+        }
       }
     });
   }
@@ -693,11 +1262,140 @@ module.exports = class Zbox {
     return new Promise((resolve, reject) => {
       try {
         utils.ensureObject(arg);
-        utils.ensureString2(arg.uri, arg.pwd);
-        zbox.repairSuperBlock(arg.uri, arg.pwd);
+        utils.ensureString(arg.uri);
+        utils.ensurePassword(arg.pwd, arg.keyFile);
+        zbox.repairSuperBlock(arg.uri, arg.pwd, arg.keyFile);
         resolve();
       } catch (err) {
         reject(err);
+      } finally {
+        if (utils.isObject(arg) && arg.wipePassword) {
+          utils.wipePassword(arg.pwd);
+        }
+      }
+    });
+  }
+
+  // Only file storage and zbox storage with a local file cache can be
+  // checked, for the latter the cached copy is checked, which can be behind
+  // the remote one. Other storages give a result with `supported` false.
+  checkSuperBlock(arg) {
+    if (this.exited) {
+      return Promise.reject(new Error('ZboxFS exited'));
+    }
+
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureObject(arg);
+        utils.ensureString(arg.uri);
+        utils.ensurePassword(arg.pwd, arg.keyFile);
+        const result = zbox.checkSuperBlock(arg.uri, arg.pwd, arg.keyFile);
+        resolve(result);
+      } catch (err) {
+        reject(err);
+      } finally {
+        if (utils.isObject(arg) && arg.wipePassword) {
+          utils.wipePassword(arg.pwd);
+        }
+      }
+    });
+  }
+
+  // Storage whose super block cannot be read locally is verified by opening
+  // the repo read-only, which needs the repo not to be opened elsewhere.
+  verifyPassword(arg) {
+    if (this.exited) {
+      return Promise.reject(new Error('ZboxFS exited'));
+    }
+
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureObject(arg);
+        utils.ensureString(arg.uri);
+        utils.ensurePassword(arg.pwd, arg.keyFile);
+        const result = zbox.verifyPassword(arg.uri, arg.pwd, arg.keyFile);
+        resolve(result);
+      } catch (err) {
+        reject(err);
+      } finally {
+        if (utils.isObject(arg) && arg.wipePassword) {
+          utils.wipePassword(arg.pwd);
+        }
+      }
+    });
+  }
+
+  migrateRepo(params) {
+    const args = params;
+
+    if (this.exited) {
+      return Promise.reject(new Error('ZboxFS exited'));
+    }
+
+    return new Promise((resolve, reject) => {
+      try {
+        utils.ensureObject(args);
+        utils.ensureString2(args.fromUri, args.toUri);
+        utils.ensurePassword(args.fromPwd, args.fromKeyFile);
+        utils.ensurePassword(args.toPwd, args.toKeyFile);
+        args.opts = args.opts || {};
+        utils.ensureObject(args.opts);
+        utils.ensureOptionalFunction(args.onProgress);
+
+        const report = zbox.migrateRepo(args, args.onProgress);
+        resolve(report);
+      } catch (err) {
+        reject(err);
+      } finally {
+        if (utils.isObject(args) && args.wipePassword) {
+          utils.wipePassword(args.fromPwd, args.toPwd);
+        }
+      }
+    });
+  }
+
+  mirror(srcRepo, dstRepo, opts = {}) {
+    if (this.exited) {
+      return Promise.reject(new Error('ZboxFS exited'));
+    }
+
+    return new Promise((resolve, reject) => {
+      try {
+        if (!(srcRepo instanceof Repo) || !(dstRepo instanceof Repo)) {
+          throw new Error('Wrong argument, repo required');
+        }
+        utils.ensureObject(opts);
+        if (opts.path !== undefined) utils.ensureString(opts.path);
+        const report = zbox.mirror(srcRepo.inner, dstRepo.inner, opts);
+        resolve(report);
+      } catch (err) {
+        reject(err);
+      }
+    });
+  }
+
+  diffTrees(repoA, pathA, repoB, pathB, opts = {}) {
+    if (this.exited) {
+      return Promise.reject(new Error('ZboxFS exited'));
+    }
+
+    return new Promise((resolve, reject) => {
+      try {
+        if (!(repoA instanceof Repo) || !(repoB instanceof Repo)) {
+          throw new Error('Wrong argument, repo required');
+        }
+        utils.ensureString2(pathA, pathB);
+        utils.ensureObject(opts);
+        const diff = zbox.diffTrees(
+          repoA.inner,
+          pathA,
+          repoB.inner,
+          pathB,
+          !!opts.compareContent
+        );
+        resolve(diff);
+      } catch (err) {
+        reject(err);
       }
     });
   }
@@ -13,6 +13,11 @@ exports.isString = s => {
   return typeof s === 'string';
 };
 
+// check if it is Date
+exports.isDate = d => {
+  return d instanceof Date;
+};
+
 // check if it is array buffer view
 exports.isArrayBufferView = value => {
   return (
@@ -24,7 +29,7 @@ exports.isArrayBufferView = value => {
 
 // check if it is Node.js Buffer
 exports.isBuffer = value => {
-  return Buffer.isBUffer(value);
+  return Buffer.isBuffer(value);
 };
 
 // check if it is ArrayBuffer, TypedArray or Buffer
@@ -61,6 +66,16 @@ exports.ensureNumber = n => {
   }
 };
 
+// largest chunk size of streaming reads, same as in native code
+const MAX_CHUNK_SIZE = 64 * 1024 * 1024;
+
+// ensure arg is an integer chunk size in 1..=MAX_CHUNK_SIZE
+exports.ensureChunkSize = n => {
+  if (!Number.isInteger(n) || n < 1 || n > MAX_CHUNK_SIZE) {
+    throw new Error('Wrong argument, chunk size out of range');
+  }
+};
+
 // ensure two args are number
 exports.ensureNumber2 = (n, n2) => {
   if (!exports.isNumber(n) || !exports.isNumber(n2)) {
@@ -82,6 +97,70 @@ exports.ensureString2 = (s, s2) => {
   }
 };
 
+// ensure password is given as string or buffer-like type, or by a key file
+exports.ensurePassword = (pwd, keyFile) => {
+  if (keyFile !== undefined) {
+    exports.ensureString(keyFile);
+    if (pwd !== undefined) {
+      throw new Error('Wrong argument, password and key file both given');
+    }
+  } else if (!exports.isString(pwd) && !exports.isBufType(pwd)) {
+    throw new Error('Wrong argument, string or buffer-like type required');
+  }
+};
+
+// zero-fill buffer-like passwords in place, strings are immutable and left
+exports.wipePassword = (...pwds) => {
+  pwds.forEach(pwd => {
+    if (exports.isBufType(pwd)) {
+      new Uint8Array(pwd.buffer, pwd.byteOffset, pwd.byteLength).fill(0);
+    }
+  });
+};
+
+// ensure arg is Date or number of seconds since epoch, return seconds
+exports.ensureTime = t => {
+  if (exports.isDate(t)) {
+    return Math.floor(t.getTime() / 1000);
+  }
+  if (!exports.isNumber(t)) {
+    throw new Error('Wrong argument, Date or number required');
+  }
+  return t;
+};
+
+// ensure arg is a function if it is provided
+exports.ensureOptionalFunction = f => {
+  if (f !== undefined && typeof f !== 'function') {
+    throw new Error('Wrong argument, function required');
+  }
+};
+
+// ensure arg is an array of strings
+exports.ensureStringArray = arr => {
+  if (!Array.isArray(arr) || !arr.every(exports.isString)) {
+    throw new Error('Wrong argument, array of strings required');
+  }
+};
+
+// ensure arg is history prune policy, return native options
+exports.ensurePrunePolicy = policy => {
+  exports.ensureObject(policy);
+  if (policy.keep === undefined && policy.olderThan === undefined) {
+    throw new Error('Wrong argument, keep or olderThan required');
+  }
+
+  const opts = {};
+  if (policy.keep !== undefined) {
+    exports.ensureNumber(policy.keep);
+    opts.keep = policy.keep;
+  }
+  if (policy.olderThan !== undefined) {
+    opts.olderThan = exports.ensureTime(policy.olderThan);
+  }
+  return opts;
+};
+
 // ensure arg is ArrayBuffer, or TypedArray, or Buffer
 exports.ensureBufType = buf => {
   if (!exports.isBufType(buf)) {
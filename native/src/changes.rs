@@ -0,0 +1,209 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use zbox::{Error, Repo};
+
+use meta;
+use time_to_f64;
+use walk;
+
+// metadata entry name of removal log, one JSON record per line
+const REMOVALS_META: &str = "removals.jsonl";
+
+// maximum number of removals kept in the log, oldest ones are dropped first
+const MAX_REMOVALS: usize = 10_000;
+
+// log length which triggers dropping oldest removals, it is compacted to
+// half of it so compaction only happens once in many removals
+const COMPACT_LEN: usize = 2 * 1024 * 1024;
+
+/// Removal of a path done through this binding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Removal {
+    pub path: String,
+    pub is_dir: bool,
+    pub removed_at: f64,
+
+    /// Set if the path was moved away rather than deleted
+    pub renamed_to: Option<String>,
+}
+
+/// Kind of a change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+    Renamed,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Renamed => "renamed",
+        }
+    }
+}
+
+/// A file or directory changed since a point in time.
+#[derive(Debug)]
+pub struct Change {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub is_dir: bool,
+    pub changed_at: f64,
+
+    /// Current version number, only for existing files
+    pub curr_version: Option<usize>,
+
+    /// Numbers of versions created since the point in time
+    pub versions: Vec<usize>,
+
+    /// Original path of a renamed entry
+    pub renamed_from: Option<String>,
+}
+
+// append a removal to the log, compact it if it grew too long
+fn append_removal(repo: &mut Repo, removal: &Removal) -> Result<(), Error> {
+    let len = meta::append(repo, REMOVALS_META, removal)?;
+    if len > COMPACT_LEN {
+        meta::compact_log(repo, REMOVALS_META, MAX_REMOVALS, COMPACT_LEN / 2)?;
+    }
+    Ok(())
+}
+
+/// Record removal of a path in the removal log.
+///
+/// Per-file metadata of the path, such as version notes, is dropped, or
+/// moved along if it is renamed.
+/// This is best-effort: the removal is already done when it is recorded, so
+/// failing to record it doesn't fail the removal.
+pub fn record_removal(
+    repo: &mut Repo,
+    path: &str,
+    is_dir: bool,
+    renamed_to: Option<&str>,
+) {
+    let _ =
+        meta::file_removed(repo, Path::new(path), renamed_to.map(Path::new));
+    let _ = append_removal(
+        repo,
+        &Removal {
+            path: path.to_owned(),
+            is_dir,
+            removed_at: time_to_f64(SystemTime::now()),
+            renamed_to: renamed_to.map(|to| to.to_owned()),
+        },
+    );
+}
+
+/// List entries under a path changed at or after a point in time.
+///
+/// Creation and modification are detected from entry metadata and file
+/// history. Removals are only known if they were done through this binding
+/// and are still in the removal log, and are reported only for paths which
+/// don't exist any more. A path removed and then re-created is classified
+/// by metadata of the new entry like any other entry, which is created as
+/// its creation time is after the removal. Changes are ordered by time.
+pub fn changes_since(
+    repo: &mut Repo,
+    path: &Path,
+    time: f64,
+) -> Result<Vec<Change>, Error> {
+    let mut changes = Vec::new();
+
+    // collect created and modified entries
+    {
+        let repo: &Repo = repo;
+        walk::walk(repo, path, &mut |ent| {
+            let md = ent.metadata();
+            let created_at = time_to_f64(md.created_at());
+            let modified_at = time_to_f64(md.modified_at());
+            let kind = if created_at >= time {
+                ChangeKind::Created
+            } else if modified_at >= time {
+                ChangeKind::Modified
+            } else {
+                return Ok(());
+            };
+
+            let path = ent.path().to_str().unwrap().to_owned();
+            let (curr_version, versions) = if md.is_file() {
+                let versions = repo
+                    .history(&path)?
+                    .iter()
+                    .filter(|ver| time_to_f64(ver.created_at()) >= time)
+                    .map(|ver| ver.num())
+                    .collect();
+                (Some(md.curr_version()), versions)
+            } else {
+                (None, Vec::new())
+            };
+
+            changes.push(Change {
+                path,
+                kind,
+                is_dir: md.is_dir(),
+                changed_at: modified_at,
+                curr_version,
+                versions,
+                renamed_from: None,
+            });
+            Ok(())
+        })?;
+    }
+
+    // add tracked removals and renames
+    let removals: Vec<Removal> = meta::load_log(repo, REMOVALS_META)?;
+    for removal in removals {
+        if removal.removed_at < time {
+            continue;
+        }
+
+        if let Some(ref to) = removal.renamed_to {
+            if Path::new(to).starts_with(path)
+                && !changes.iter().any(|chg| chg.path == *to)
+                && repo.path_exists(to)?
+            {
+                let md = repo.metadata(to)?;
+                changes.push(Change {
+                    path: to.clone(),
+                    kind: ChangeKind::Renamed,
+                    is_dir: removal.is_dir,
+                    changed_at: removal.removed_at,
+                    curr_version: if md.is_file() {
+                        Some(md.curr_version())
+                    } else {
+                        None
+                    },
+                    versions: Vec::new(),
+                    renamed_from: Some(removal.path.clone()),
+                });
+            }
+        }
+
+        // an existing path is reported by the walk above
+        if Path::new(&removal.path).starts_with(path)
+            && !repo.path_exists(&removal.path)?
+        {
+            changes.retain(|chg| {
+                !(chg.kind == ChangeKind::Removed && chg.path == removal.path)
+            });
+            changes.push(Change {
+                path: removal.path,
+                kind: ChangeKind::Removed,
+                is_dir: removal.is_dir,
+                changed_at: removal.removed_at,
+                curr_version: None,
+                versions: Vec::new(),
+                renamed_from: None,
+            });
+        }
+    }
+
+    changes.sort_by(|a, b| a.changed_at.partial_cmp(&b.changed_at).unwrap());
+    Ok(changes)
+}
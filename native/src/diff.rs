@@ -0,0 +1,148 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::str;
+
+use similar::{Algorithm, TextDiff};
+
+use zbox::{Error, VersionReader};
+
+use read_full;
+
+// size of the sniffing window and the binary comparison chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// number of unchanged lines shown around each hunk in unified diff
+const CONTEXT_RADIUS: usize = 3;
+
+/// Difference between two versions of a file.
+pub enum VersionDiff {
+    /// Line-based unified diff of two text versions
+    Text(String),
+
+    /// Differing byte ranges, as (offset, len), of two binary versions
+    Binary {
+        ranges: Vec<(usize, usize)>,
+        len_a: usize,
+        len_b: usize,
+    },
+}
+
+// check if the leading bytes look like UTF-8 text
+fn looks_like_text(buf: &[u8]) -> bool {
+    if buf.contains(&0) {
+        return false;
+    }
+    match str::from_utf8(buf) {
+        Ok(_) => true,
+        // a multi-byte char may be cut off at the end of sniffing window
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
+// compare two readers byte by byte and collect the differing ranges
+fn diff_bytes<R: Read, S: Read>(
+    a: &mut R,
+    b: &mut S,
+    len_a: usize,
+    len_b: usize,
+) -> Result<Vec<(usize, usize)>, Error> {
+    let mut ranges = Vec::new();
+    let mut buf_a = vec![0u8; CHUNK_SIZE];
+    let mut buf_b = vec![0u8; CHUNK_SIZE];
+    let mut pos = 0;
+    let mut start: Option<usize> = None;
+
+    loop {
+        let read_a = read_full(a, &mut buf_a)?;
+        let read_b = read_full(b, &mut buf_b)?;
+        let read = read_a.min(read_b);
+
+        for i in 0..read {
+            match (buf_a[i] != buf_b[i], start) {
+                (true, None) => start = Some(pos + i),
+                (false, Some(s)) => {
+                    ranges.push((s, pos + i - s));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        pos += read;
+
+        if read_a < CHUNK_SIZE || read_b < CHUNK_SIZE {
+            break;
+        }
+    }
+
+    // the tail of the longer version is all changed
+    let end = len_a.max(len_b);
+    if pos < end && start.is_none() {
+        start = Some(pos);
+    }
+    if let Some(s) = start {
+        ranges.push((s, end.max(pos) - s));
+    }
+
+    Ok(ranges)
+}
+
+/// Compare two versions of a file.
+///
+/// Text versions are compared line by line and produce a unified diff,
+/// others are compared byte by byte and produce changed byte ranges.
+pub fn diff_versions(
+    path: &str,
+    a: &mut VersionReader,
+    b: &mut VersionReader,
+) -> Result<VersionDiff, Error> {
+    let ver_a = a.version()?;
+    let ver_b = b.version()?;
+    let len_a = ver_a.content_len();
+    let len_b = ver_b.content_len();
+
+    // sniff the leading chunk of both versions to detect text
+    let mut head_a = vec![0u8; CHUNK_SIZE];
+    let mut head_b = vec![0u8; CHUNK_SIZE];
+    let read_a = read_full(a, &mut head_a)?;
+    let read_b = read_full(b, &mut head_b)?;
+    a.seek(SeekFrom::Start(0))?;
+    b.seek(SeekFrom::Start(0))?;
+
+    if looks_like_text(&head_a[..read_a]) && looks_like_text(&head_b[..read_b])
+    {
+        let mut buf_a = Vec::with_capacity(len_a);
+        let mut buf_b = Vec::with_capacity(len_b);
+        a.read_to_end(&mut buf_a)?;
+        b.read_to_end(&mut buf_b)?;
+
+        if let (Ok(text_a), Ok(text_b)) =
+            (str::from_utf8(&buf_a), str::from_utf8(&buf_b))
+        {
+            let header_a = format!("{}@{}", path, ver_a.num());
+            let header_b = format!("{}@{}", path, ver_b.num());
+            let unified = TextDiff::configure()
+                .algorithm(Algorithm::Myers)
+                .diff_lines(text_a, text_b)
+                .unified_diff()
+                .context_radius(CONTEXT_RADIUS)
+                .header(&header_a, &header_b)
+                .to_string();
+            return Ok(VersionDiff::Text(unified));
+        }
+
+        // not valid UTF-8 after all, compare the buffered content as binary
+        let ranges =
+            diff_bytes(&mut &buf_a[..], &mut &buf_b[..], len_a, len_b)?;
+        return Ok(VersionDiff::Binary {
+            ranges,
+            len_a,
+            len_b,
+        });
+    }
+
+    let ranges = diff_bytes(a, b, len_a, len_b)?;
+    Ok(VersionDiff::Binary {
+        ranges,
+        len_a,
+        len_b,
+    })
+}
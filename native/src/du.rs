@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+use zbox::{Error, Repo};
+
+use walk;
+
+/// Usage totals of a directory tree.
+#[derive(Debug, Default, Clone)]
+pub struct Usage {
+    /// Content bytes of current file versions
+    pub bytes: usize,
+
+    /// Content bytes of all retained file versions
+    pub history_bytes: usize,
+
+    pub files: usize,
+    pub dirs: usize,
+    pub versions: usize,
+}
+
+impl Usage {
+    fn add_file(
+        &mut self,
+        bytes: usize,
+        history_bytes: usize,
+        versions: usize,
+    ) {
+        self.bytes += bytes;
+        self.history_bytes += history_bytes;
+        self.files += 1;
+        self.versions += versions;
+    }
+}
+
+/// Disk usage of a directory tree.
+#[derive(Debug, Default)]
+pub struct DiskUsage {
+    pub total: Usage,
+
+    /// Usage of each direct sub-directory, keyed by its path
+    pub subtrees: BTreeMap<String, Usage>,
+
+    /// Largest files as (path, bytes), biggest first
+    pub largest: Vec<(String, usize)>,
+}
+
+/// Compute disk usage of the tree under a directory.
+pub fn disk_usage(
+    repo: &Repo,
+    path: &Path,
+    top: usize,
+) -> Result<DiskUsage, Error> {
+    let mut du = DiskUsage::default();
+
+    walk::walk(repo, path, &mut |ent| {
+        let ent_path = ent.path().to_str().unwrap().to_owned();
+        let md = ent.metadata();
+
+        // find the direct sub-directory this entry belongs to
+        let mut rel = ent.path().strip_prefix(path).unwrap().components();
+        let subtree = match (rel.next(), rel.next()) {
+            (Some(Component::Normal(name)), Some(_)) => {
+                Some(path.join(name).to_str().unwrap().to_owned())
+            }
+            _ => None,
+        };
+
+        if md.is_dir() {
+            du.total.dirs += 1;
+            if let Some(subtree) = subtree {
+                du.subtrees.entry(subtree).or_default().dirs += 1;
+            } else {
+                du.subtrees.entry(ent_path).or_default();
+            }
+            return Ok(());
+        }
+
+        let bytes = md.content_len();
+        let hist = repo.history(&ent_path)?;
+        let history_bytes = hist.iter().map(|ver| ver.content_len()).sum();
+        du.total.add_file(bytes, history_bytes, hist.len());
+        if let Some(subtree) = subtree {
+            du.subtrees.entry(subtree).or_default().add_file(
+                bytes,
+                history_bytes,
+                hist.len(),
+            );
+        }
+
+        // keep only the top largest files, trimming in batches
+        du.largest.push((ent_path, bytes));
+        if du.largest.len() >= top.max(1) * 2 {
+            du.largest.sort_by(|a, b| b.1.cmp(&a.1));
+            du.largest.truncate(top);
+        }
+        Ok(())
+    })?;
+
+    du.largest.sort_by(|a, b| b.1.cmp(&a.1));
+    du.largest.truncate(top);
+    Ok(du)
+}
@@ -0,0 +1,123 @@
+use std::path::{Component, Path, PathBuf};
+
+use globset::{GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+
+use zbox::{DirEntry, Error, Repo};
+
+use changes;
+use walk::{WalkOptions, Walker};
+
+// characters which make a path component a pattern
+const META_CHARS: &[char] = &['*', '?', '[', ']', '{', '}'];
+
+/// Compiled glob pattern with its ignore patterns.
+pub struct Glob {
+    // deepest literal directory of the pattern, where the walk starts
+    base: PathBuf,
+
+    // walk depth limit, none if the pattern contains `**`
+    max_depth: Option<usize>,
+
+    matcher: GlobMatcher,
+    ignore: GlobSet,
+}
+
+fn build_glob(pattern: &str) -> Result<globset::Glob, Error> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(|_| Error::InvalidArgument)
+}
+
+impl Glob {
+    /// Compile an absolute glob pattern and the patterns to be ignored.
+    ///
+    /// `*` and `?` do not match `/`, `**` matches any number of directories.
+    /// A directory matching an ignore pattern is skipped with all its
+    /// descendants.
+    pub fn new(pattern: &str, ignore: &[String]) -> Result<Self, Error> {
+        let path = Path::new(pattern);
+        if !path.has_root() {
+            return Err(Error::InvalidArgument);
+        }
+
+        // split the pattern into literal base dir and the matching part
+        let comps: Vec<Component> = path.components().skip(1).collect();
+        let literal = comps
+            .iter()
+            .take_while(|comp| {
+                !comp.as_os_str().to_str().unwrap().contains(META_CHARS)
+            })
+            .count()
+            .min(comps.len().saturating_sub(1));
+        let mut base = PathBuf::from("/");
+        base.extend(&comps[..literal]);
+        let rest = &comps[literal..];
+        let max_depth = if rest.iter().any(|comp| comp.as_os_str() == "**") {
+            None
+        } else {
+            Some(rest.len())
+        };
+
+        let matcher = build_glob(pattern)?.compile_matcher();
+        let mut builder = GlobSetBuilder::new();
+        for pat in ignore {
+            builder.add(build_glob(pat)?);
+        }
+        let ignore = builder.build().map_err(|_| Error::InvalidArgument)?;
+
+        Ok(Glob {
+            base,
+            max_depth,
+            matcher,
+            ignore,
+        })
+    }
+
+    /// Find all entries matching the pattern, in pre-order.
+    pub fn find(&self, repo: &Repo) -> Result<Vec<DirEntry>, Error> {
+        let mut matches = Vec::new();
+        if !repo.is_dir(&self.base)? {
+            return Ok(matches);
+        }
+
+        let opts = WalkOptions {
+            max_depth: self.max_depth,
+            ..Default::default()
+        };
+        let mut walker = Walker::new(&self.base, opts)?;
+        while let Some((ent, _)) = walker.next(repo)? {
+            if self.ignore.is_match(ent.path()) {
+                walker.skip_dir();
+                continue;
+            }
+            if self.matcher.is_match(ent.path()) {
+                matches.push(ent);
+            }
+        }
+        Ok(matches)
+    }
+}
+
+/// Remove all entries matching the pattern, return the removed paths.
+///
+/// Matched directories are removed with all their content, entries inside
+/// an already removed directory are not reported again.
+pub fn remove(repo: &mut Repo, glob: &Glob) -> Result<Vec<String>, Error> {
+    let mut removed: Vec<String> = Vec::new();
+    for ent in glob.find(repo)? {
+        if removed.iter().any(|rm| ent.path().starts_with(rm)) {
+            continue;
+        }
+        let path = ent.path().to_str().unwrap().to_owned();
+        let is_dir = ent.metadata().is_dir();
+        if is_dir {
+            repo.remove_dir_all(&path)?;
+        } else {
+            repo.remove_file(&path)?;
+        }
+        changes::record_removal(repo, &path, is_dir, None);
+        removed.push(path);
+    }
+    Ok(removed)
+}
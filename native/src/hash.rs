@@ -0,0 +1,91 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+use blake2::Blake2b512;
+use sha2::{Digest, Sha256};
+
+use zbox::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+// size of chunks fed to the hasher
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Content hash algorithm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HashAlgo {
+    Sha256,
+    Blake2b,
+}
+
+impl FromStr for HashAlgo {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(HashAlgo::Sha256),
+            "blake2b" => Ok(HashAlgo::Blake2b),
+            _ => Err(Error::InvalidArgument),
+        }
+    }
+}
+
+fn digest<D: Digest, R: Read>(
+    rdr: &mut R,
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let mut hasher = D::new();
+
+    // content may be key material, so wipe the buffer after use
+    let mut buf = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+    loop {
+        let read = rdr.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+
+    // the hash is copied out and the finalized output wiped, as a hash of
+    // key file content is used as password
+    let mut out = hasher.finalize();
+    let hash = Zeroizing::new(out.to_vec());
+    out.as_mut_slice().zeroize();
+    Ok(hash)
+}
+
+/// Hash all content of a reader, returns lower case hex string.
+pub fn hash_reader<R: Read>(
+    algo: HashAlgo,
+    rdr: &mut R,
+) -> Result<String, Error> {
+    let hash = match algo {
+        HashAlgo::Sha256 => digest::<Sha256, R>(rdr)?,
+        HashAlgo::Blake2b => digest::<Blake2b512, R>(rdr)?,
+    };
+    Ok(to_hex(&hash))
+}
+
+/// Encode bytes as lower case hex string.
+///
+/// The string is allocated once at its full size, so no copy of the content
+/// is left behind by reallocation.
+pub fn to_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        hex.push(DIGITS[(b >> 4) as usize] as char);
+        hex.push(DIGITS[(b & 0xf) as usize] as char);
+    }
+    hex
+}
+
+/// Derive a password from content of a host key file.
+///
+/// The file is hashed with SHA-256, so key files of any size and content
+/// can be used.
+pub fn key_file_password(path: &Path) -> Result<Zeroizing<String>, Error> {
+    let mut file = fs::File::open(path)?;
+    let hash = digest::<Sha256, _>(&mut file)?;
+    Ok(Zeroizing::new(to_hex(&hash)))
+}
@@ -0,0 +1,367 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use zbox::{Error, Repo};
+
+use stage;
+use time_to_f64;
+use transfer::{Progress, TransferError};
+use version_at;
+use walk;
+
+// size of chunks streamed between host files and repo files
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Callbacks driving a host import.
+pub trait ImportHooks {
+    /// Decide whether to import a repo path, skipping a dir skips its content
+    fn filter(&mut self, path: &str, is_dir: bool) -> Result<bool, Error>;
+
+    /// Report progress after an entry is imported
+    fn progress(&mut self, progress: &Progress) -> Result<(), Error>;
+}
+
+/// Host entry skipped during import.
+#[derive(Debug)]
+pub struct Skipped {
+    pub path: String,
+    pub reason: &'static str,
+}
+
+/// Outcome of a host import.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub files: usize,
+    pub dirs: usize,
+    pub bytes: usize,
+    pub skipped: Vec<Skipped>,
+}
+
+// host entry planned for import
+struct Planned {
+    host: PathBuf,
+    path: PathBuf,
+    is_dir: bool,
+}
+
+// collect host entries to import, in pre-order
+fn plan<H: ImportHooks>(
+    host: &Path,
+    path: &Path,
+    hooks: &mut H,
+    planned: &mut Vec<Planned>,
+    report: &mut ImportReport,
+) -> Result<(), TransferError> {
+    let fail_at = |err: Error| TransferError::new(err, path, 0);
+
+    let mut ents = fs::read_dir(host)
+        .and_then(|ents| ents.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| fail_at(Error::from(err)))?;
+    ents.sort_by_key(|ent| ent.file_name());
+
+    for ent in ents {
+        let host = ent.path();
+        let name = match ent.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => {
+                report.skipped.push(Skipped {
+                    path: host.to_string_lossy().into_owned(),
+                    reason: "non UTF-8 file name",
+                });
+                continue;
+            }
+        };
+        let file_type =
+            ent.file_type().map_err(|err| fail_at(Error::from(err)))?;
+        let reason = if file_type.is_symlink() {
+            Some("symbolic link")
+        } else if !file_type.is_dir() && !file_type.is_file() {
+            Some("special file")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            report.skipped.push(Skipped {
+                path: host.to_string_lossy().into_owned(),
+                reason,
+            });
+            continue;
+        }
+
+        let path = path.join(name);
+        let is_dir = file_type.is_dir();
+        if !hooks
+            .filter(path.to_str().unwrap(), is_dir)
+            .map_err(|err| TransferError::new(err, &path, 0))?
+        {
+            continue;
+        }
+        planned.push(Planned {
+            host: host.clone(),
+            path: path.clone(),
+            is_dir,
+        });
+        if is_dir {
+            plan(&host, &path, hooks, planned, report)?;
+        }
+    }
+    Ok(())
+}
+
+// stream a host file into a repo file
+fn import_file(
+    repo: &mut Repo,
+    host: &Path,
+    path: &Path,
+    overwrite: bool,
+) -> Result<usize, Error> {
+    let mut src = fs::File::open(host)?;
+
+    if repo.path_exists(path)? {
+        if repo.is_dir(path)? {
+            return Err(Error::IsDir);
+        }
+        if !overwrite {
+            return Err(Error::AlreadyExists);
+        }
+        let version_limit = repo.info()?.version_limit();
+        let len = src.metadata()?.len() as usize;
+        stage::write_version(repo, &mut src, path, version_limit)?;
+        return Ok(len);
+    }
+
+    let mut file = repo.create_file(path)?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut written = 0;
+    loop {
+        let read = src.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buf[..read])?;
+        written += read;
+    }
+    if written > 0 {
+        file.finish()?;
+    }
+    Ok(written)
+}
+
+/// Import a host file or directory tree into the repo.
+///
+/// Files are streamed in chunks, each one becomes a single new version.
+/// Symbolic links and special files are skipped and reported. The import
+/// stops at the first failure, reporting the repo path it failed on.
+pub fn import_from<H: ImportHooks>(
+    repo: &mut Repo,
+    host: &Path,
+    path: &Path,
+    overwrite: bool,
+    hooks: &mut H,
+) -> Result<ImportReport, TransferError> {
+    let mut report = ImportReport::default();
+    let md = fs::symlink_metadata(host)
+        .map_err(|err| TransferError::new(Error::from(err), path, 0))?;
+
+    let mut planned = Vec::new();
+    if md.is_dir() {
+        repo.create_dir_all(path)
+            .map_err(|err| TransferError::new(err, path, 0))?;
+        plan(host, path, hooks, &mut planned, &mut report)?;
+    } else if md.is_file() {
+        planned.push(Planned {
+            host: host.to_path_buf(),
+            path: path.to_path_buf(),
+            is_dir: false,
+        });
+    } else {
+        return Err(TransferError::new(Error::InvalidArgument, path, 0));
+    }
+
+    let total = planned.len();
+    for (done, ent) in planned.iter().enumerate() {
+        let result = if ent.is_dir {
+            repo.create_dir_all(&ent.path).map(|_| report.dirs += 1)
+        } else {
+            import_file(repo, &ent.host, &ent.path, overwrite).map(|len| {
+                report.files += 1;
+                report.bytes += len;
+            })
+        };
+        result
+            .and_then(|_| {
+                hooks.progress(&Progress {
+                    done: done + 1,
+                    total,
+                    path: ent.path.to_str().unwrap(),
+                })
+            })
+            .map_err(|err| TransferError::new(err, &ent.path, done))?;
+    }
+
+    Ok(report)
+}
+
+/// Outcome of a host export.
+#[derive(Debug, Default)]
+pub struct ExportReport {
+    pub files: usize,
+    pub dirs: usize,
+    pub bytes: usize,
+
+    /// Files not existing yet at the requested time
+    pub skipped: Vec<String>,
+}
+
+// repo entry planned for export
+struct Exported {
+    path: PathBuf,
+    host: PathBuf,
+    is_dir: bool,
+}
+
+// convert seconds since epoch to host file time
+fn host_time(secs: f64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs as u64)
+}
+
+// stream one version of a repo file out to a host file, return its length
+fn export_file(
+    repo: &mut Repo,
+    path: &Path,
+    host: &Path,
+    time: Option<f64>,
+    overwrite: bool,
+) -> Result<Option<usize>, Error> {
+    let file = repo.open_file(path)?;
+    let (mut rdr, mtime) = match time {
+        Some(time) => match version_at(&file.history()?, time) {
+            Ok(ver_num) => {
+                let rdr = file.version_reader(ver_num)?;
+                let created_at = time_to_f64(rdr.version()?.created_at());
+                (rdr, created_at)
+            }
+            Err(Error::NoVersion) => return Ok(None),
+            Err(err) => return Err(err),
+        },
+        None => {
+            let ver_num = file.metadata()?.curr_version();
+            let modified_at = time_to_f64(repo.metadata(path)?.modified_at());
+            (file.version_reader(ver_num)?, modified_at)
+        }
+    };
+
+    if host.is_dir() {
+        return Err(Error::IsDir);
+    }
+    if host.exists() && !overwrite {
+        return Err(Error::AlreadyExists);
+    }
+
+    let mut dst = fs::File::create(host)?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut written = 0;
+    loop {
+        let read = rdr.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        dst.write_all(&buf[..read])?;
+        written += read;
+    }
+    dst.set_modified(host_time(mtime))?;
+    Ok(Some(written))
+}
+
+/// Export a repo file or directory tree to the host file system.
+///
+/// Files are written with the latest content, or with the version current
+/// at `time` if it is specified, files not existing at that time are
+/// skipped. Host modification times are set from the repo. The export stops
+/// at the first failure, reporting the repo path it failed on.
+pub fn export_to<F>(
+    repo: &mut Repo,
+    path: &Path,
+    host: &Path,
+    time: Option<f64>,
+    overwrite: bool,
+    progress: &mut F,
+) -> Result<ExportReport, TransferError>
+where
+    F: FnMut(&Progress) -> Result<(), Error>,
+{
+    let fail_at = |err: Error| TransferError::new(err, path, 0);
+
+    let mut report = ExportReport::default();
+    let mut planned = Vec::new();
+    if repo.is_dir(path).map_err(fail_at)? {
+        fs::create_dir_all(host).map_err(|err| fail_at(Error::from(err)))?;
+        walk::walk(repo, path, &mut |ent| {
+            let rel = ent.path().strip_prefix(path).unwrap();
+            planned.push(Exported {
+                path: ent.path().to_path_buf(),
+                host: host.join(rel),
+                is_dir: ent.metadata().is_dir(),
+            });
+            Ok(())
+        })
+        .map_err(fail_at)?;
+    } else {
+        planned.push(Exported {
+            path: path.to_path_buf(),
+            host: host.to_path_buf(),
+            is_dir: false,
+        });
+    }
+
+    let total = planned.len();
+    for (done, ent) in planned.iter().enumerate() {
+        let result = if ent.is_dir {
+            fs::create_dir_all(&ent.host)
+                .map_err(Error::from)
+                .map(|_| report.dirs += 1)
+        } else {
+            export_file(repo, &ent.path, &ent.host, time, overwrite).map(
+                |written| match written {
+                    Some(len) => {
+                        report.files += 1;
+                        report.bytes += len;
+                    }
+                    None => report
+                        .skipped
+                        .push(ent.path.to_str().unwrap().to_owned()),
+                },
+            )
+        };
+        result
+            .and_then(|_| {
+                progress(&Progress {
+                    done: done + 1,
+                    total,
+                    path: ent.path.to_str().unwrap(),
+                })
+            })
+            .map_err(|err| TransferError::new(err, &ent.path, done))?;
+    }
+
+    // directory times are set last, as writing children changes them
+    if time.is_none() {
+        let mut dirs: Vec<&Exported> =
+            planned.iter().filter(|ent| ent.is_dir).collect();
+        dirs.reverse();
+        for ent in dirs {
+            let md = repo
+                .metadata(&ent.path)
+                .map_err(|err| TransferError::new(err, &ent.path, total))?;
+
+            // best effort, not all platforms can open a dir to set its time
+            let mtime = host_time(time_to_f64(md.modified_at()));
+            let _ = fs::File::open(&ent.host)
+                .and_then(|dir| dir.set_modified(mtime));
+        }
+    }
+
+    Ok(report)
+}
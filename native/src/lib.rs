@@ -1,23 +1,85 @@
 #[macro_use]
 extern crate neon;
+extern crate blake2;
+extern crate globset;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate sha2;
+extern crate similar;
+extern crate tar as tar_lib;
 extern crate zbox;
+extern crate zeroize;
+extern crate zip as zip_lib;
+
+mod changes;
+mod diff;
+mod du;
+mod glob;
+mod hash;
+mod host;
+mod meta;
+mod migrate;
+mod mirror;
+mod notes;
+mod page;
+mod prune;
+mod snapshot;
+mod stage;
+mod super_block;
+mod tar;
+mod transfer;
+mod tree_diff;
+mod verify;
+mod walk;
+mod zip;
 
 use std::error::Error as StdError;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 
 use neon::prelude::*;
+use neon::result::Throw;
+use zeroize::Zeroizing;
+
+use changes::Change;
+use diff::VersionDiff;
+use du::{DiskUsage, Usage};
+use glob::Glob;
+use hash::HashAlgo;
+use host::{ExportReport, ImportHooks, ImportReport};
+use meta::FileOptions;
+use migrate::MigrateReport;
+use mirror::{MirrorOptions, MirrorReport};
+use notes::FileNotes;
+use page::{PageOptions, SortBy};
+use prune::{PrunePolicy, PruneReport};
+use snapshot::{RestoreReport, Snapshot};
+use super_block::SuperBlockCheck;
+use tar::{TarExporter, TarImporter, UnpackReport};
+use transfer::{CopyOptions, Progress, TransferError};
+use tree_diff::TreeDiff;
+use verify::VerifyReport;
+use walk::{WalkOptions, WalkOrder, Walker};
+use zip::ZipOptions;
 
 use zbox::{
-    self as zbox_lib, Cipher, Error, File, MemLimit, Metadata,
+    self as zbox_lib, Cipher, DirEntry, Error, File, MemLimit, Metadata,
     OpenOptions, OpsLimit, Repo, RepoOpener, Version, VersionReader,
 };
 
 type Wrapper<T> = Arc<Mutex<Option<Box<T>>>>;
 
+// largest chunk size of streaming reads, same as in lib/utils.js
+const MAX_CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+// the flag is set while the repo is lent out, see `lend_repo`
 #[derive(Clone)]
-pub struct RepoWrapper(Wrapper<Repo>);
+pub struct RepoWrapper(Wrapper<Repo>, Arc<AtomicBool>);
 
 #[derive(Clone)]
 pub struct FileWrapper(Wrapper<File>);
@@ -25,11 +87,66 @@ pub struct FileWrapper(Wrapper<File>);
 #[derive(Clone)]
 pub struct VersionReaderWrapper(Wrapper<VersionReader>);
 
+#[derive(Clone)]
+pub struct TarReaderWrapper {
+    repo: Wrapper<Repo>,
+    exporter: Wrapper<TarExporter>,
+}
+
+#[derive(Clone)]
+pub struct TarWriterWrapper {
+    repo: Wrapper<Repo>,
+    importer: Wrapper<TarImporter>,
+}
+
+#[derive(Clone)]
+pub struct WalkerWrapper {
+    repo: Wrapper<Repo>,
+    walker: Wrapper<Walker>,
+}
+
 #[inline]
 fn time_to_f64(t: SystemTime) -> f64 {
     t.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as f64
 }
 
+// rename a path and record it, a file or empty directory replaced by the
+// rename is recorded as removed
+fn rename(repo: &mut Repo, from: &str, to: &str) -> Result<(), Error> {
+    let is_dir = repo.is_dir(from)?;
+    let replaced = if repo.path_exists(to)? {
+        Some(repo.is_dir(to)?)
+    } else {
+        None
+    };
+    repo.rename(from, to)?;
+    if let Some(to_is_dir) = replaced {
+        changes::record_removal(repo, to, to_is_dir, None);
+    }
+    changes::record_removal(repo, from, is_dir, Some(to));
+    Ok(())
+}
+
+// check a chunk size from JS is an integer in 1..=MAX_CHUNK_SIZE
+fn to_chunk_size(size: f64) -> Result<usize, Error> {
+    if size.fract() != 0.0 || size < 1.0 || size > MAX_CHUNK_SIZE as f64 {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(size as usize)
+}
+
+// read until the buffer is full or reached EOF
+fn read_full<R: Read>(rdr: &mut R, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut read = 0;
+    while read < buf.len() {
+        match rdr.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
 #[inline]
 fn error_string(err: Error) -> String {
     let desc = err.description().to_owned();
@@ -57,61 +174,96 @@ fn open_repo(mut cx: FunctionContext) -> JsResult<JsNumber> {
         .get(&mut cx, "uri")?
         .downcast_or_throw::<JsString, FunctionContext>(&mut cx)?
         .value();
-    let pwd = args
-        .get(&mut cx, "pwd")?
-        .downcast_or_throw::<JsString, FunctionContext>(&mut cx)?
-        .value();
+    let pwd = args.get(&mut cx, "pwd")?;
     let opts = args
         .get(&mut cx, "opts")?
         .downcast_or_throw::<JsObject, FunctionContext>(&mut cx)?;
+    let key_file = opts
+        .get(&mut cx, "keyFile")?
+        .downcast::<JsString>()
+        .ok()
+        .map(|path| path.value());
+    let pwd = password_from_js(&mut cx, pwd, key_file)?;
 
     let mut opener = RepoOpener::new();
-    if let Ok(limit) = opts.get(&mut cx, "opsLimit")?.downcast::<JsNumber>() {
+    set_opener_options(&mut cx, &mut opener, opts)?;
+
+    opener
+        .open(&uri, &pwd)
+        .or_else(|err| cx.throw_error(error_string(err)))
+        .map(|repo| {
+            let ptr_num = Box::into_raw(Box::new(repo)) as i64;
+            cx.number(ptr_num as f64)
+        })
+}
+
+// get password from either a string or binary password, or a key file
+//
+// zbox only takes string passwords, so binary passwords are hex-encoded
+// and key files are hashed into one. The encoded string is the actual repo
+// password: a binary password differs from the same bytes given as string,
+// and equals its own lower case hex text given as string. The password is
+// wiped from memory when dropped.
+fn password_from_js<'a, C: Context<'a>>(
+    cx: &mut C,
+    pwd: Handle<'a, JsValue>,
+    key_file: Option<String>,
+) -> NeonResult<Zeroizing<String>> {
+    let has_pwd = !pwd.is_a::<JsUndefined>() && !pwd.is_a::<JsNull>();
+    match key_file {
+        Some(_) if has_pwd => {
+            cx.throw_error(error_string(Error::InvalidArgument))
+        }
+        Some(path) => hash::key_file_password(Path::new(&path))
+            .or_else(|err| cx.throw_error(error_string(err))),
+        None => {
+            if let Ok(pwd) = pwd.downcast::<JsString>() {
+                return Ok(Zeroizing::new(pwd.value()));
+            }
+            let buf = pwd.downcast_or_throw::<JsBuffer, C>(cx)?;
+            let pwd = cx.borrow(&buf, |data| hash::to_hex(data.as_slice()));
+            Ok(Zeroizing::new(pwd))
+        }
+    }
+}
+
+// apply repo open options in a JS object to an opener
+fn set_opener_options<'a, C: Context<'a>>(
+    cx: &mut C,
+    opener: &mut RepoOpener,
+    opts: Handle<'a, JsObject>,
+) -> NeonResult<()> {
+    if let Ok(limit) = opts.get(cx, "opsLimit")?.downcast::<JsNumber>() {
         opener.ops_limit(OpsLimit::from(limit.value() as i32));
     }
-    if let Ok(limit) = opts.get(&mut cx, "memLimit")?.downcast::<JsNumber>() {
+    if let Ok(limit) = opts.get(cx, "memLimit")?.downcast::<JsNumber>() {
         opener.mem_limit(MemLimit::from(limit.value() as i32));
     }
-    if let Ok(cipher) = opts.get(&mut cx, "cipher")?.downcast::<JsNumber>() {
+    if let Ok(cipher) = opts.get(cx, "cipher")?.downcast::<JsNumber>() {
         opener.cipher(Cipher::from(cipher.value() as i32));
     }
-    if let Ok(create) = opts.get(&mut cx, "create")?.downcast::<JsBoolean>() {
+    if let Ok(create) = opts.get(cx, "create")?.downcast::<JsBoolean>() {
         opener.create(create.value());
     }
-    if let Ok(create_new) =
-        opts.get(&mut cx, "createNew")?.downcast::<JsBoolean>()
-    {
+    if let Ok(create_new) = opts.get(cx, "createNew")?.downcast::<JsBoolean>() {
         opener.create_new(create_new.value());
     }
-    if let Ok(compress) = opts.get(&mut cx, "compress")?.downcast::<JsBoolean>()
-    {
+    if let Ok(compress) = opts.get(cx, "compress")?.downcast::<JsBoolean>() {
         opener.compress(compress.value());
     }
-    if let Ok(limit) = opts.get(&mut cx, "versionLimit")?.downcast::<JsNumber>()
-    {
+    if let Ok(limit) = opts.get(cx, "versionLimit")?.downcast::<JsNumber>() {
         opener.version_limit(limit.value() as u8);
     }
-    if let Ok(dedup) = opts.get(&mut cx, "dedupChunk")?.downcast::<JsBoolean>()
-    {
+    if let Ok(dedup) = opts.get(cx, "dedupChunk")?.downcast::<JsBoolean>() {
         opener.dedup_chunk(dedup.value());
     }
-    if let Ok(read_only) =
-        opts.get(&mut cx, "readOnly")?.downcast::<JsBoolean>()
-    {
+    if let Ok(read_only) = opts.get(cx, "readOnly")?.downcast::<JsBoolean>() {
         opener.read_only(read_only.value());
     }
-    if let Ok(force) = opts.get(&mut cx, "force")?.downcast::<JsBoolean>()
-    {
+    if let Ok(force) = opts.get(cx, "force")?.downcast::<JsBoolean>() {
         opener.force(force.value());
     }
-
-    opener
-        .open(&uri, &pwd)
-        .or_else(|err| cx.throw_error(error_string(err)))
-        .map(|repo| {
-            let ptr_num = Box::into_raw(Box::new(repo)) as i64;
-            cx.number(ptr_num as f64)
-        })
+    Ok(())
 }
 
 #[allow(dead_code)]
@@ -125,12 +277,235 @@ fn repo_exists(mut cx: FunctionContext) -> JsResult<JsBoolean> {
 #[allow(dead_code)]
 fn repair_super_block(mut cx: FunctionContext) -> JsResult<JsUndefined> {
     let uri = cx.argument::<JsString>(0)?.value();
-    let pwd = cx.argument::<JsString>(1)?.value();
+    let key_file = cx
+        .argument_opt(2)
+        .and_then(|arg| arg.downcast::<JsString>().ok())
+        .map(|path| path.value());
+    let pwd = cx.argument::<JsValue>(1)?;
+    let pwd = password_from_js(&mut cx, pwd, key_file)?;
     Repo::repair_super_block(&uri, &pwd)
         .or_else(|err| cx.throw_error(error_string(err)))
         .and_then(|_| Ok(cx.undefined()))
 }
 
+#[allow(dead_code)]
+fn verify_password(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    let uri = cx.argument::<JsString>(0)?.value();
+    let key_file = cx
+        .argument_opt(2)
+        .and_then(|arg| arg.downcast::<JsString>().ok())
+        .map(|path| path.value());
+    let pwd = cx.argument::<JsValue>(1)?;
+    let pwd = password_from_js(&mut cx, pwd, key_file)?;
+
+    // password is right if any super block copy can be decrypted with it,
+    // even if the other one is damaged
+    let check = match super_block::check(&uri, &pwd)
+        .or_else(|err| cx.throw_error(error_string(err)))?
+    {
+        Some(check) => check,
+        None => {
+            // super block cannot be read locally, so open the repo read-only
+            // and close it straight away
+            return match RepoOpener::new().read_only(true).open(&uri, &pwd) {
+                Ok(repo) => {
+                    drop(repo);
+                    Ok(cx.boolean(true))
+                }
+                Err(Error::Decrypt) => Ok(cx.boolean(false)),
+                Err(err) => cx.throw_error(error_string(err)),
+            };
+        }
+    };
+    if check.arms.iter().any(|arm| arm.seq.is_some()) {
+        return Ok(cx.boolean(true));
+    }
+    let errs: Vec<Error> =
+        check.arms.into_iter().filter_map(|arm| arm.err).collect();
+    if errs.iter().any(|err| match err {
+        Error::Decrypt => true,
+        _ => false,
+    }) {
+        return Ok(cx.boolean(false));
+    }
+    let err = errs.into_iter().next().unwrap_or(Error::InvalidSuperBlk);
+    cx.throw_error(error_string(err))
+}
+
+#[allow(dead_code)]
+fn check_super_block(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let uri = cx.argument::<JsString>(0)?.value();
+    let key_file = cx
+        .argument_opt(2)
+        .and_then(|arg| arg.downcast::<JsString>().ok())
+        .map(|path| path.value());
+    let pwd = cx.argument::<JsValue>(1)?;
+    let pwd = password_from_js(&mut cx, pwd, key_file)?;
+
+    let check = super_block::check(&uri, &pwd)
+        .or_else(|err| cx.throw_error(error_string(err)))?;
+    Ok(super_block_check_to_js_obj(&mut cx, check))
+}
+
+#[allow(dead_code)]
+fn migrate_repo(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let args = cx.argument::<JsObject>(0)?;
+    let callback = cx
+        .argument_opt(1)
+        .and_then(|arg| arg.downcast::<JsFunction>().ok());
+
+    let from_uri = args
+        .get(&mut cx, "fromUri")?
+        .downcast_or_throw::<JsString, FunctionContext>(&mut cx)?
+        .value();
+    let from_key_file = args
+        .get(&mut cx, "fromKeyFile")?
+        .downcast::<JsString>()
+        .ok()
+        .map(|path| path.value());
+    let from_pwd = args.get(&mut cx, "fromPwd")?;
+    let from_pwd = password_from_js(&mut cx, from_pwd, from_key_file)?;
+    let to_uri = args
+        .get(&mut cx, "toUri")?
+        .downcast_or_throw::<JsString, FunctionContext>(&mut cx)?
+        .value();
+    let to_key_file = args
+        .get(&mut cx, "toKeyFile")?
+        .downcast::<JsString>()
+        .ok()
+        .map(|path| path.value());
+    let to_pwd = args.get(&mut cx, "toPwd")?;
+    let to_pwd = password_from_js(&mut cx, to_pwd, to_key_file)?;
+    let opts = args
+        .get(&mut cx, "opts")?
+        .downcast_or_throw::<JsObject, FunctionContext>(&mut cx)?;
+    let all_versions = args
+        .get(&mut cx, "allVersions")?
+        .downcast::<JsBoolean>()
+        .map(|val| val.value())
+        .unwrap_or(false);
+
+    // target must be a new repo unless told otherwise by the options
+    let mut opener = RepoOpener::new();
+    opener.create_new(true);
+    set_opener_options(&mut cx, &mut opener, opts)?;
+    let existed = Repo::exists(&to_uri)
+        .or_else(|err| cx.throw_error(error_string(err)))?;
+
+    let mut src = RepoOpener::new()
+        .read_only(true)
+        .open(&from_uri, &from_pwd)
+        .or_else(|err| cx.throw_error(error_string(err)))?;
+    let mut dst = opener
+        .open(&to_uri, &to_pwd)
+        .or_else(|err| cx.throw_error(error_string(err)))?;
+
+    // both repos stay open while the callback runs, so it cannot open them
+    let mut thrown = false;
+    let result =
+        migrate::migrate(&mut src, &mut dst, all_versions, &mut |progress| {
+            call_progress(&mut cx, callback, progress, &mut thrown)
+        });
+
+    // a partly filled new target would make a retry fail, so remove it
+    if result.is_err() && !existed {
+        drop(dst);
+        let _ = Repo::destroy(&to_uri);
+    }
+    match result {
+        Ok(report) => Ok(migrate_report_to_js_obj(&mut cx, &report)),
+        Err(_) if thrown => Err(Throw),
+        Err(err) => throw_transfer_error(&mut cx, err),
+    }
+}
+
+#[allow(dead_code)]
+fn mirror_repo(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let src = cx.argument::<JsRepo>(0)?;
+    let dst = cx.argument::<JsRepo>(1)?;
+    let opts = cx.argument::<JsObject>(2)?;
+
+    let mut options = MirrorOptions::default();
+    if let Ok(path) = opts.get(&mut cx, "path")?.downcast::<JsString>() {
+        options.path = path.value().into();
+    }
+    if let Ok(delete) = opts
+        .get(&mut cx, "deleteExtraneous")?
+        .downcast::<JsBoolean>()
+    {
+        options.delete_extraneous = delete.value();
+    }
+    if let Ok(dry_run) = opts.get(&mut cx, "dryRun")?.downcast::<JsBoolean>() {
+        options.dry_run = dry_run.value();
+    }
+
+    let (src, dst) = {
+        let guard = cx.lock();
+        let src = src.borrow(&guard).0.clone();
+        let dst = dst.borrow(&guard).0.clone();
+        (src, dst)
+    };
+    if Arc::ptr_eq(&src, &dst) {
+        return cx.throw_error(error_string(Error::InvalidArgument));
+    }
+
+    let result = {
+        let mut src = src.lock().unwrap();
+        let mut dst = dst.lock().unwrap();
+        match (&mut *src, &mut *dst) {
+            (Some(ref mut src), Some(ref mut dst)) => {
+                mirror::mirror(src, dst, &options)
+            }
+            _ => Err(Error::RepoClosed),
+        }
+    };
+    result
+        .or_else(|err| cx.throw_error(error_string(err)))
+        .map(|report| mirror_report_to_js_obj(&mut cx, &report))
+}
+
+#[allow(dead_code)]
+fn diff_trees(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let repo_a = cx.argument::<JsRepo>(0)?;
+    let path_a = cx.argument::<JsString>(1)?.value();
+    let repo_b = cx.argument::<JsRepo>(2)?;
+    let path_b = cx.argument::<JsString>(3)?.value();
+    let compare_content = cx.argument::<JsBoolean>(4)?.value();
+
+    let (repo_a, repo_b) = {
+        let guard = cx.lock();
+        let repo_a = repo_a.borrow(&guard).0.clone();
+        let repo_b = repo_b.borrow(&guard).0.clone();
+        (repo_a, repo_b)
+    };
+    let (path_a, path_b) = (Path::new(&path_a), Path::new(&path_b));
+
+    // each repo is locked on its own, so both sides can be the same repo
+    let result = with_repo(&repo_a, |repo| tree_diff::list(repo, path_a))
+        .and_then(|listing_a| {
+            let listing_b =
+                with_repo(&repo_b, |repo| tree_diff::list(repo, path_b))?;
+            Ok(tree_diff::compare(&listing_a, &listing_b))
+        })
+        .and_then(|(mut diff, same_len)| {
+            if compare_content {
+                let hashes_a = with_repo(&repo_a, |repo| {
+                    tree_diff::hash_files(repo, path_a, &same_len)
+                })?;
+                let hashes_b = with_repo(&repo_b, |repo| {
+                    tree_diff::hash_files(repo, path_b, &same_len)
+                })?;
+                tree_diff::add_content_changes(
+                    &mut diff, same_len, &hashes_a, &hashes_b,
+                );
+            }
+            Ok(diff)
+        });
+    result
+        .or_else(|err| cx.throw_error(error_string(err)))
+        .map(|diff| tree_diff_to_js_obj(&mut cx, &diff))
+}
+
 fn metadata_to_js_obj<'a, C: Context<'a>>(
     cx: &mut C,
     md: Metadata,
@@ -150,86 +525,1240 @@ fn metadata_to_js_obj<'a, C: Context<'a>>(
     meta
 }
 
+fn version_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    version: &Version,
+) -> Handle<'a, JsObject> {
+    let js_ver = cx.empty_object();
+    let val = cx.number(version.num() as f64);
+    js_ver.set(cx, "num", val).unwrap();
+    let val = cx.number(version.content_len() as f64);
+    js_ver.set(cx, "contentLen", val).unwrap();
+    let val = cx.number(time_to_f64(version.created_at()));
+    js_ver.set(cx, "createdAt", val).unwrap();
+    js_ver
+}
+
 fn hist_to_js_array<'a, C: Context<'a>>(
     cx: &mut C,
     hist: Vec<Version>,
+    notes: &FileNotes,
 ) -> Handle<'a, JsArray> {
     let js_array = cx.empty_array();
     for (i, version) in hist.iter().enumerate() {
-        let js_ver = cx.empty_object();
-        let val = cx.number(version.num() as f64);
-        js_ver.set(cx, "num", val).unwrap();
-        let val = cx.number(version.content_len() as f64);
-        js_ver.set(cx, "contentLen", val).unwrap();
-        let val = cx.number(time_to_f64(version.created_at()));
-        js_ver.set(cx, "createdAt", val).unwrap();
+        let js_ver = version_to_js_obj(cx, version);
+        let note = notes.get(&version.num()).cloned().unwrap_or_default();
+        if let Some(message) = note.message {
+            let val = cx.string(message);
+            js_ver.set(cx, "message", val).unwrap();
+        }
+        if let Some(author) = note.author {
+            let val = cx.string(author);
+            js_ver.set(cx, "author", val).unwrap();
+        }
+        let js_labels = cx.empty_array();
+        for (j, label) in note.labels.iter().enumerate() {
+            let val = cx.string(label);
+            js_labels.set(cx, j as u32, val).unwrap();
+        }
+        js_ver.set(cx, "labels", js_labels).unwrap();
         js_array.set(cx, i as u32, js_ver).unwrap();
     }
     js_array
 }
 
-declare_types! {
-    pub class JsRepo for RepoWrapper {
-        init(mut cx) {
-            let ptr_num = cx.argument::<JsNumber>(0)?.value() as i64;
-            let repo = unsafe { Box::from_raw(ptr_num as *mut Repo) };
-            Ok(RepoWrapper(Arc::new(Mutex::new(Some(repo)))))
+fn version_diff_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    diff: VersionDiff,
+) -> Handle<'a, JsObject> {
+    let js_diff = cx.empty_object();
+    match diff {
+        VersionDiff::Text(unified) => {
+            let val = cx.string("text");
+            js_diff.set(cx, "kind", val).unwrap();
+            let val = cx.string(unified);
+            js_diff.set(cx, "unified", val).unwrap();
+        }
+        VersionDiff::Binary {
+            ranges,
+            len_a,
+            len_b,
+        } => {
+            let val = cx.string("binary");
+            js_diff.set(cx, "kind", val).unwrap();
+            let val = cx.number(len_a as f64);
+            js_diff.set(cx, "lenA", val).unwrap();
+            let val = cx.number(len_b as f64);
+            js_diff.set(cx, "lenB", val).unwrap();
+            let js_ranges = cx.empty_array();
+            for (i, (offset, len)) in ranges.into_iter().enumerate() {
+                let js_range = cx.empty_object();
+                let val = cx.number(offset as f64);
+                js_range.set(cx, "offset", val).unwrap();
+                let val = cx.number(len as f64);
+                js_range.set(cx, "len", val).unwrap();
+                js_ranges.set(cx, i as u32, js_range).unwrap();
+            }
+            js_diff.set(cx, "ranges", js_ranges).unwrap();
+        }
+    }
+    js_diff
+}
+
+fn strings_from_js<'a, C: Context<'a>>(
+    cx: &mut C,
+    arr: Handle<'a, JsArray>,
+) -> NeonResult<Vec<String>> {
+    let mut strs = Vec::new();
+    for val in arr.to_vec(cx)? {
+        strs.push(val.downcast_or_throw::<JsString, C>(cx)?.value());
+    }
+    Ok(strs)
+}
+
+fn strings_to_js_array<'a, C: Context<'a>>(
+    cx: &mut C,
+    strs: &[String],
+) -> Handle<'a, JsArray> {
+    let js_arr = cx.empty_array();
+    for (i, s) in strs.iter().enumerate() {
+        let val = cx.string(s);
+        js_arr.set(cx, i as u32, val).unwrap();
+    }
+    js_arr
+}
+
+fn prune_policy_from_js<'a, C: Context<'a>>(
+    cx: &mut C,
+    opts: Handle<'a, JsObject>,
+) -> NeonResult<PrunePolicy> {
+    let mut policy = PrunePolicy::default();
+    if let Ok(keep) = opts.get(cx, "keep")?.downcast::<JsNumber>() {
+        policy.keep = Some(keep.value() as usize);
+    }
+    if let Ok(time) = opts.get(cx, "olderThan")?.downcast::<JsNumber>() {
+        policy.older_than = Some(time.value());
+    }
+    Ok(policy)
+}
+
+fn prune_report_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    report: PruneReport,
+) -> Handle<'a, JsObject> {
+    let js_report = cx.empty_object();
+    let val = cx.number(report.files as f64);
+    js_report.set(cx, "filesPruned", val).unwrap();
+    let val = cx.number(report.versions as f64);
+    js_report.set(cx, "versionsRemoved", val).unwrap();
+    let val = cx.number(report.bytes as f64);
+    js_report.set(cx, "bytesRemoved", val).unwrap();
+    let js_renumbered = cx.empty_object();
+    for (path, renumber) in report.renumbered.iter() {
+        let js_renumber = cx.empty_object();
+        for (old_num, new_num) in renumber.iter() {
+            let val = cx.number(*new_num as f64);
+            js_renumber.set(cx, &*old_num.to_string(), val).unwrap();
+        }
+        js_renumbered.set(cx, path.as_str(), js_renumber).unwrap();
+    }
+    js_report.set(cx, "renumbered", js_renumbered).unwrap();
+    js_report
+}
+
+fn snapshot_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    snapshot: &Snapshot,
+    with_entries: bool,
+) -> Handle<'a, JsObject> {
+    let js_snapshot = cx.empty_object();
+    let val = cx.string(&snapshot.name);
+    js_snapshot.set(cx, "name", val).unwrap();
+    let val = cx.number(snapshot.created_at);
+    js_snapshot.set(cx, "createdAt", val).unwrap();
+    let val = cx.number(snapshot.dirs.len() as f64);
+    js_snapshot.set(cx, "dirCount", val).unwrap();
+    let val = cx.number(snapshot.files.len() as f64);
+    js_snapshot.set(cx, "fileCount", val).unwrap();
+
+    if with_entries {
+        let js_dirs = cx.empty_array();
+        for (i, dir) in snapshot.dirs.iter().enumerate() {
+            let val = cx.string(dir);
+            js_dirs.set(cx, i as u32, val).unwrap();
+        }
+        js_snapshot.set(cx, "dirs", js_dirs).unwrap();
+
+        let js_files = cx.empty_array();
+        for (i, (path, ver_num)) in snapshot.files.iter().enumerate() {
+            let js_file = cx.empty_object();
+            let val = cx.string(path);
+            js_file.set(cx, "path", val).unwrap();
+            let val = cx.number(*ver_num as f64);
+            js_file.set(cx, "version", val).unwrap();
+            js_files.set(cx, i as u32, js_file).unwrap();
+        }
+        js_snapshot.set(cx, "files", js_files).unwrap();
+    }
+
+    js_snapshot
+}
+
+fn restore_report_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    report: RestoreReport,
+) -> Handle<'a, JsObject> {
+    let js_report = cx.empty_object();
+    let val = cx.number(report.restored as f64);
+    js_report.set(cx, "restored", val).unwrap();
+    let val = cx.number(report.removed as f64);
+    js_report.set(cx, "removed", val).unwrap();
+    let js_missing = cx.empty_array();
+    for (i, path) in report.missing.iter().enumerate() {
+        let val = cx.string(path);
+        js_missing.set(cx, i as u32, val).unwrap();
+    }
+    js_report.set(cx, "missing", js_missing).unwrap();
+    js_report
+}
+
+fn dir_entry_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    ent: &DirEntry,
+) -> Handle<'a, JsObject> {
+    let js_ent = cx.empty_object();
+    let path = cx.string(ent.path().to_str().unwrap().to_owned());
+    js_ent.set(cx, "path", path).unwrap();
+    let file_name = cx.string(ent.file_name().to_owned());
+    js_ent.set(cx, "fileName", file_name).unwrap();
+    let md = metadata_to_js_obj(cx, ent.metadata());
+    js_ent.set(cx, "metadata", md).unwrap();
+    js_ent
+}
+
+fn usage_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    usage: &Usage,
+) -> Handle<'a, JsObject> {
+    let js_usage = cx.empty_object();
+    let val = cx.number(usage.bytes as f64);
+    js_usage.set(cx, "totalBytes", val).unwrap();
+    let val = cx.number(usage.history_bytes as f64);
+    js_usage.set(cx, "historyBytes", val).unwrap();
+    let val = cx.number(usage.files as f64);
+    js_usage.set(cx, "fileCount", val).unwrap();
+    let val = cx.number(usage.dirs as f64);
+    js_usage.set(cx, "dirCount", val).unwrap();
+    let val = cx.number(usage.versions as f64);
+    js_usage.set(cx, "versionCount", val).unwrap();
+    js_usage
+}
+
+fn disk_usage_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    du: &DiskUsage,
+) -> Handle<'a, JsObject> {
+    let js_du = usage_to_js_obj(cx, &du.total);
+
+    let js_largest = cx.empty_array();
+    for (i, (path, bytes)) in du.largest.iter().enumerate() {
+        let js_file = cx.empty_object();
+        let val = cx.string(path);
+        js_file.set(cx, "path", val).unwrap();
+        let val = cx.number(*bytes as f64);
+        js_file.set(cx, "size", val).unwrap();
+        js_largest.set(cx, i as u32, js_file).unwrap();
+    }
+    js_du.set(cx, "largestFiles", js_largest).unwrap();
+
+    let js_subtrees = cx.empty_array();
+    for (i, (path, usage)) in du.subtrees.iter().enumerate() {
+        let js_usage = usage_to_js_obj(cx, usage);
+        let val = cx.string(path);
+        js_usage.set(cx, "path", val).unwrap();
+        js_subtrees.set(cx, i as u32, js_usage).unwrap();
+    }
+    js_du.set(cx, "subtrees", js_subtrees).unwrap();
+
+    js_du
+}
+
+// call the optional JS progress callback, a throw from it stops the operation
+fn call_progress<'a, C: Context<'a>>(
+    cx: &mut C,
+    callback: Option<Handle<'a, JsFunction>>,
+    progress: &Progress,
+    thrown: &mut bool,
+) -> Result<(), Error> {
+    let callback = match callback {
+        Some(callback) => callback,
+        None => return Ok(()),
+    };
+    let js_progress = cx.empty_object();
+    let val = cx.number(progress.done as f64);
+    js_progress.set(cx, "done", val).unwrap();
+    let val = cx.number(progress.total as f64);
+    js_progress.set(cx, "total", val).unwrap();
+    let val = cx.string(progress.path);
+    js_progress.set(cx, "path", val).unwrap();
+
+    let this = cx.undefined();
+    match callback.call(cx, this, vec![js_progress]) {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            *thrown = true;
+            Err(Error::Io(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "Stopped by progress callback",
+            )))
+        }
+    }
+}
+
+// import hooks calling optional JS filter and progress callbacks
+struct JsImportHooks<'a, 'b, C: Context<'a> + 'b> {
+    cx: &'b mut C,
+    filter: Option<Handle<'a, JsFunction>>,
+    progress: Option<Handle<'a, JsFunction>>,
+    thrown: bool,
+}
+
+impl<'a, 'b, C: Context<'a>> ImportHooks for JsImportHooks<'a, 'b, C> {
+    fn filter(&mut self, path: &str, is_dir: bool) -> Result<bool, Error> {
+        let filter = match self.filter {
+            Some(filter) => filter,
+            None => return Ok(true),
+        };
+        let js_path = self.cx.string(path).upcast::<JsValue>();
+        let js_is_dir = self.cx.boolean(is_dir).upcast::<JsValue>();
+        let this = self.cx.undefined();
+        match filter.call(self.cx, this, vec![js_path, js_is_dir]) {
+            Ok(ret) => Ok(ret
+                .downcast::<JsBoolean>()
+                .map(|val| val.value())
+                .unwrap_or(false)),
+            Err(_) => {
+                self.thrown = true;
+                Err(Error::Io(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "Stopped by filter callback",
+                )))
+            }
+        }
+    }
+
+    fn progress(&mut self, progress: &Progress) -> Result<(), Error> {
+        call_progress(self.cx, self.progress, progress, &mut self.thrown)
+    }
+}
+
+fn import_report_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    report: &ImportReport,
+) -> Handle<'a, JsObject> {
+    let js_report = cx.empty_object();
+    let val = cx.number(report.files as f64);
+    js_report.set(cx, "files", val).unwrap();
+    let val = cx.number(report.dirs as f64);
+    js_report.set(cx, "dirs", val).unwrap();
+    let val = cx.number(report.bytes as f64);
+    js_report.set(cx, "bytes", val).unwrap();
+    let js_skipped = cx.empty_array();
+    for (i, skipped) in report.skipped.iter().enumerate() {
+        let js_skip = cx.empty_object();
+        let val = cx.string(&skipped.path);
+        js_skip.set(cx, "path", val).unwrap();
+        let val = cx.string(skipped.reason);
+        js_skip.set(cx, "reason", val).unwrap();
+        js_skipped.set(cx, i as u32, js_skip).unwrap();
+    }
+    js_report.set(cx, "skipped", js_skipped).unwrap();
+    js_report
+}
+
+fn migrate_report_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    report: &MigrateReport,
+) -> Handle<'a, JsObject> {
+    let js_report = cx.empty_object();
+    let val = cx.number(report.files as f64);
+    js_report.set(cx, "files", val).unwrap();
+    let val = cx.number(report.dirs as f64);
+    js_report.set(cx, "dirs", val).unwrap();
+    let val = cx.number(report.versions as f64);
+    js_report.set(cx, "versions", val).unwrap();
+    let val = cx.number(report.bytes as f64);
+    js_report.set(cx, "bytes", val).unwrap();
+    js_report
+}
+
+fn mirror_report_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    report: &MirrorReport,
+) -> Handle<'a, JsObject> {
+    let js_report = cx.empty_object();
+    let val = strings_to_js_array(cx, &report.added);
+    js_report.set(cx, "added", val).unwrap();
+    let val = strings_to_js_array(cx, &report.modified);
+    js_report.set(cx, "modified", val).unwrap();
+    let val = strings_to_js_array(cx, &report.deleted);
+    js_report.set(cx, "deleted", val).unwrap();
+    let val = cx.number(report.bytes as f64);
+    js_report.set(cx, "bytes", val).unwrap();
+    js_report
+}
+
+fn tree_diff_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    diff: &TreeDiff,
+) -> Handle<'a, JsObject> {
+    let js_diff = cx.empty_object();
+    let val = strings_to_js_array(cx, &diff.added);
+    js_diff.set(cx, "added", val).unwrap();
+    let val = strings_to_js_array(cx, &diff.removed);
+    js_diff.set(cx, "removed", val).unwrap();
+    let val = strings_to_js_array(cx, &diff.changed);
+    js_diff.set(cx, "changed", val).unwrap();
+    js_diff
+}
+
+fn verify_report_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    report: VerifyReport,
+) -> Handle<'a, JsObject> {
+    let js_report = cx.empty_object();
+    let val = cx.number(report.files as f64);
+    js_report.set(cx, "files", val).unwrap();
+    let val = cx.number(report.dirs as f64);
+    js_report.set(cx, "dirs", val).unwrap();
+    let val = cx.number(report.versions as f64);
+    js_report.set(cx, "versions", val).unwrap();
+    let val = cx.number(report.bytes as f64);
+    js_report.set(cx, "bytes", val).unwrap();
+    let js_problems = cx.empty_array();
+    for (i, problem) in report.problems.into_iter().enumerate() {
+        let js_problem = cx.empty_object();
+        let val = cx.string(&problem.path);
+        js_problem.set(cx, "path", val).unwrap();
+        if let Some(version) = problem.version {
+            let val = cx.number(version as f64);
+            js_problem.set(cx, "version", val).unwrap();
+        }
+        let val = cx.string(error_string(problem.err));
+        js_problem.set(cx, "error", val).unwrap();
+        js_problems.set(cx, i as u32, js_problem).unwrap();
+    }
+    js_report.set(cx, "problems", js_problems).unwrap();
+    js_report
+}
+
+// an unsupported storage has no copies and unknown consistency
+fn super_block_check_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    check: Option<SuperBlockCheck>,
+) -> Handle<'a, JsObject> {
+    let js_check = cx.empty_object();
+    let val = cx.boolean(check.is_some());
+    js_check.set(cx, "supported", val).unwrap();
+    let val: Handle<JsValue> = match check {
+        Some(ref check) => cx.boolean(check.is_consistent()).upcast(),
+        None => cx.null().upcast(),
+    };
+    js_check.set(cx, "consistent", val).unwrap();
+    let js_copies = cx.empty_array();
+    let arms = check.map(|check| check.arms).unwrap_or_default();
+    for (i, arm) in arms.into_iter().enumerate() {
+        let js_copy = cx.empty_object();
+        let val = cx.boolean(arm.present);
+        js_copy.set(cx, "present", val).unwrap();
+        let val = cx.boolean(arm.seq.is_some());
+        js_copy.set(cx, "readable", val).unwrap();
+        let val: Handle<JsValue> = match arm.seq {
+            Some(seq) => cx.number(seq as f64).upcast(),
+            None => cx.null().upcast(),
+        };
+        js_copy.set(cx, "seq", val).unwrap();
+        let val: Handle<JsValue> = match arm.err {
+            Some(err) => cx.string(error_string(err)).upcast(),
+            None => cx.null().upcast(),
+        };
+        js_copy.set(cx, "error", val).unwrap();
+        js_copies.set(cx, i as u32, js_copy).unwrap();
+    }
+    js_check.set(cx, "copies", js_copies).unwrap();
+    js_check
+}
+
+fn export_report_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    report: &ExportReport,
+) -> Handle<'a, JsObject> {
+    let js_report = cx.empty_object();
+    let val = cx.number(report.files as f64);
+    js_report.set(cx, "files", val).unwrap();
+    let val = cx.number(report.dirs as f64);
+    js_report.set(cx, "dirs", val).unwrap();
+    let val = cx.number(report.bytes as f64);
+    js_report.set(cx, "bytes", val).unwrap();
+    let js_skipped = cx.empty_array();
+    for (i, path) in report.skipped.iter().enumerate() {
+        let val = cx.string(path);
+        js_skipped.set(cx, i as u32, val).unwrap();
+    }
+    js_report.set(cx, "skipped", js_skipped).unwrap();
+    js_report
+}
+
+fn unpack_report_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    report: &UnpackReport,
+) -> Handle<'a, JsObject> {
+    let js_report = cx.empty_object();
+    let val = cx.number(report.files as f64);
+    js_report.set(cx, "files", val).unwrap();
+    let val = cx.number(report.dirs as f64);
+    js_report.set(cx, "dirs", val).unwrap();
+    let val = cx.number(report.bytes as f64);
+    js_report.set(cx, "bytes", val).unwrap();
+    let js_skipped = cx.empty_array();
+    for (i, name) in report.skipped.iter().enumerate() {
+        let val = cx.string(name);
+        js_skipped.set(cx, i as u32, val).unwrap();
+    }
+    js_report.set(cx, "skipped", js_skipped).unwrap();
+    js_report
+}
+
+// throw error of a tree operation, with the failed path and completed count
+fn throw_transfer_error<'a, C: Context<'a>, T>(
+    cx: &mut C,
+    err: TransferError,
+) -> NeonResult<T> {
+    let js_err = JsError::error(cx, error_string(err.err))?;
+    let val = cx.string(&err.path);
+    js_err.set(cx, "path", val)?;
+    let val = cx.number(err.done as f64);
+    js_err.set(cx, "done", val)?;
+    cx.throw(js_err)
+}
+
+fn change_to_js_obj<'a, C: Context<'a>>(
+    cx: &mut C,
+    change: &Change,
+) -> Handle<'a, JsObject> {
+    let js_change = cx.empty_object();
+    let val = cx.string(&change.path);
+    js_change.set(cx, "path", val).unwrap();
+    let val = cx.string(change.kind.as_str());
+    js_change.set(cx, "kind", val).unwrap();
+    let val = cx.boolean(change.is_dir);
+    js_change.set(cx, "isDir", val).unwrap();
+    let val = cx.number(change.changed_at);
+    js_change.set(cx, "changedAt", val).unwrap();
+    if let Some(curr_version) = change.curr_version {
+        let val = cx.number(curr_version as f64);
+        js_change.set(cx, "currVersion", val).unwrap();
+    }
+    let js_versions = cx.empty_array();
+    for (i, ver_num) in change.versions.iter().enumerate() {
+        let val = cx.number(*ver_num as f64);
+        js_versions.set(cx, i as u32, val).unwrap();
+    }
+    js_change.set(cx, "versions", js_versions).unwrap();
+    if let Some(ref from) = change.renamed_from {
+        let val = cx.string(from);
+        js_change.set(cx, "renamedFrom", val).unwrap();
+    }
+    js_change
+}
+
+// find the latest version which was created at or before the specified time
+fn version_at(hist: &[Version], time: f64) -> Result<usize, Error> {
+    hist.iter()
+        .filter(|ver| time_to_f64(ver.created_at()) <= time)
+        .map(|ver| ver.num())
+        .max()
+        .ok_or(Error::NoVersion)
+}
+
+// content to be hashed by a hash task
+enum HashSource {
+    File(Wrapper<File>),
+    Version(Wrapper<VersionReader>),
+    Path(Wrapper<Repo>, PathBuf),
+}
+
+// reader of a version reader shared with JS, which is locked only while a
+// chunk is read and keeps its own position, so the position seen from JS
+// is not moved
+struct SharedVersionReader<'a> {
+    vrdr: &'a Wrapper<VersionReader>,
+    pos: u64,
+}
+
+impl<'a> Read for SharedVersionReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self.vrdr.lock().unwrap() {
+            Some(ref mut vrdr) => {
+                let saved = vrdr.seek(SeekFrom::Current(0))?;
+                vrdr.seek(SeekFrom::Start(self.pos))?;
+                let read = vrdr.read(buf);
+                vrdr.seek(SeekFrom::Start(saved))?;
+                let read = read?;
+                self.pos += read as u64;
+                Ok(read)
+            }
+            None => Err(io::Error::new(io::ErrorKind::Other, Error::Closed)),
+        }
+    }
+}
+
+// background task hashing content of a file or version
+struct HashTask {
+    source: HashSource,
+    algo: HashAlgo,
+}
+
+impl Task for HashTask {
+    type Output = String;
+    type Error = Error;
+    type JsEvent = JsString;
+
+    fn perform(&self) -> Result<String, Error> {
+        // locks are only held to open a dedicated reader, or per chunk for a
+        // shared version reader, so the handles stay usable while hashing
+        match self.source {
+            HashSource::File(ref file) => {
+                let mut rdr = match *file.lock().unwrap() {
+                    Some(ref file) => {
+                        file.version_reader(file.curr_version()?)?
+                    }
+                    None => return Err(Error::Closed),
+                };
+                hash::hash_reader(self.algo, &mut rdr)
+            }
+            HashSource::Version(ref vrdr) => {
+                let mut rdr = SharedVersionReader { vrdr, pos: 0 };
+                hash::hash_reader(self.algo, &mut rdr)
+            }
+            HashSource::Path(ref repo, ref path) => {
+                let mut rdr = with_repo(repo, |repo| {
+                    let file = repo.open_file(path)?;
+                    file.version_reader(file.curr_version()?)
+                })?;
+                hash::hash_reader(self.algo, &mut rdr)
+            }
+        }
+    }
+
+    fn complete(
+        self,
+        mut cx: TaskContext,
+        result: Result<String, Error>,
+    ) -> JsResult<JsString> {
+        match result {
+            Ok(hash) => Ok(cx.string(hash)),
+            Err(err) => cx.throw_error(error_string(err)),
+        }
+    }
+}
+
+// parse hash algorithm argument, defaults to SHA-256
+fn hash_algo_from_js<'a, C: Context<'a>>(
+    cx: &mut C,
+    arg: Option<Handle<'a, JsValue>>,
+) -> NeonResult<HashAlgo> {
+    match arg {
+        Some(arg) if !arg.is_a::<JsUndefined>() => {
+            let algo = arg.downcast_or_throw::<JsString, C>(cx)?.value();
+            algo.parse()
+                .or_else(|err| cx.throw_error(error_string(err)))
+        }
+        _ => Ok(HashAlgo::Sha256),
+    }
+}
+
+// run an operation on a wrapped repo, fails if the repo is closed
+fn with_repo<T, F>(repo: &Wrapper<Repo>, f: F) -> Result<T, Error>
+where
+    F: FnOnce(&mut Repo) -> Result<T, Error>,
+{
+    let mut inner = repo.lock().unwrap();
+    match *inner {
+        Some(ref mut repo) => f(repo),
+        None => Err(Error::RepoClosed),
+    }
+}
+
+// run an operation which calls back into JS on a wrapped repo
+//
+// The repo is taken out of its wrapper while the operation runs, so that a
+// callback calling methods of the same repo fails as if it was closed rather
+// than blocking on the lock forever. Returns `None` if the repo is closed or
+// already lent out.
+fn lend_repo<T, F>(wrapper: &RepoWrapper, f: F) -> Option<T>
+where
+    F: FnOnce(&mut Repo) -> T,
+{
+    let mut repo = wrapper.0.lock().unwrap().take()?;
+    wrapper.1.store(true, Ordering::SeqCst);
+    let ret = f(&mut repo);
+    wrapper.1.store(false, Ordering::SeqCst);
+    *wrapper.0.lock().unwrap() = Some(repo);
+    Some(ret)
+}
+
+declare_types! {
+    pub class JsRepo for RepoWrapper {
+        init(mut cx) {
+            let ptr_num = cx.argument::<JsNumber>(0)?.value() as i64;
+            let repo = unsafe { Box::from_raw(ptr_num as *mut Repo) };
+            Ok(RepoWrapper(
+                Arc::new(Mutex::new(Some(repo))),
+                Arc::new(AtomicBool::new(false)),
+            ))
+        }
+
+        method close(mut cx) {
+            let this = cx.this();
+            let lent = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let lent = wrapper.1.load(Ordering::SeqCst);
+                if !lent {
+                    wrapper.0.lock().unwrap().take();
+                }
+                lent
+            };
+            if lent {
+                return cx.throw_error("Repo is busy in a running operation");
+            }
+            Ok(cx.undefined().upcast())
+        }
+
+        method info(mut cx) {
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let inner = this.borrow(&guard);
+                let wrapper = inner.0.lock().unwrap();
+                match *wrapper {
+                    Some(ref repo) => repo.info(),
+                    None => Err(Error::Closed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|info| {
+                    let info_obj = cx.empty_object();
+                    let val = cx.string(info.volume_id().to_string());
+                    info_obj.set(&mut cx, "volumeId", val)?;
+                    let val = cx.string(info.version());
+                    info_obj.set(&mut cx, "version", val)?;
+                    let val = cx.string(info.uri());
+                    info_obj.set(&mut cx, "uri", val)?;
+                    let val = cx.boolean(info.compress());
+                    info_obj.set(&mut cx, "compress", val)?;
+                    let val = cx.number(info.version_limit());
+                    info_obj.set(&mut cx, "versionLimit", val)?;
+                    let val = cx.boolean(info.dedup_chunk());
+                    info_obj.set(&mut cx, "dedupChunk", val)?;
+                    let val = cx.boolean(info.is_read_only());
+                    info_obj.set(&mut cx, "isReadOnly", val)?;
+                    let val = cx.number(time_to_f64(info.created_at()));
+                    info_obj.set(&mut cx, "createdAt", val)?;
+                    Ok(info_obj.upcast())
+                })
+        }
+
+        method resetPassword(mut cx) {
+            let old_pwd = cx.argument::<JsValue>(0)?;
+            let new_pwd = cx.argument::<JsValue>(1)?;
+            let ops_limit =
+                OpsLimit::from(cx.argument::<JsNumber>(2)?.value() as i32);
+            let mem_limit =
+                MemLimit::from(cx.argument::<JsNumber>(3)?.value() as i32);
+            let old_key_file = cx
+                .argument_opt(4)
+                .and_then(|arg| arg.downcast::<JsString>().ok())
+                .map(|path| path.value());
+            let new_key_file = cx
+                .argument_opt(5)
+                .and_then(|arg| arg.downcast::<JsString>().ok())
+                .map(|path| path.value());
+            let old_pwd = password_from_js(&mut cx, old_pwd, old_key_file)?;
+            let new_pwd = password_from_js(&mut cx, new_pwd, new_key_file)?;
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let mut inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref mut repo) => repo.reset_password(&old_pwd,
+                        &new_pwd, ops_limit, mem_limit),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|_| Ok(cx.undefined().upcast()))
+        }
+
+        method pathExists(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref repo) => repo.path_exists(&path),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|result| Ok(cx.boolean(result).upcast()))
+        }
+
+        method isFile(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref repo) => repo.is_file(&path),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|result| Ok(cx.boolean(result).upcast()))
+        }
+
+        method isDir(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref repo) => repo.is_dir(&path),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|result| Ok(cx.boolean(result).upcast()))
+        }
+
+        method createFile(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let mut inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref mut repo) => repo.create_file(&path),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|file| {
+                    let ptr_num = Box::into_raw(Box::new(file)) as i64;
+                    Ok(cx.number(ptr_num as f64).upcast())
+                })
+        }
+
+        method openFile(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let opts = cx.argument::<JsObject>(1)?;
+            let this = cx.this();
+
+            let mut options = OpenOptions::new();
+            let mut file_opts = FileOptions::default();
+            if let Ok(read) = opts.get(&mut cx, "read")?
+                .downcast::<JsBoolean>()
+            {
+                options.read(read.value());
+            }
+            if let Ok(write) = opts.get(&mut cx, "write")?
+                .downcast::<JsBoolean>()
+            {
+                options.write(write.value());
+            }
+            if let Ok(append) = opts.get(&mut cx, "append")?
+                .downcast::<JsBoolean>()
+            {
+                options.append(append.value());
+            }
+            if let Ok(truncate) = opts.get(&mut cx, "truncate")?
+                .downcast::<JsBoolean>()
+            {
+                options.truncate(truncate.value());
+            }
+            if let Ok(create) = opts.get(&mut cx, "create")?
+                .downcast::<JsBoolean>()
+            {
+                options.create(create.value());
+            }
+            if let Ok(create_new) = opts.get(&mut cx, "createNew")?
+                .downcast::<JsBoolean>()
+            {
+                options.create_new(create_new.value());
+            }
+            if let Ok(limit) = opts.get(&mut cx, "versionLimit")?
+                .downcast::<JsNumber>()
+            {
+                options.version_limit(limit.value() as u8);
+                file_opts.version_limit = Some(limit.value() as u8);
+            }
+            if let Ok(dedup) = opts.get(&mut cx, "dedupChunk")?
+                .downcast::<JsBoolean>()
+            {
+                options.dedup_chunk(dedup.value());
+                file_opts.dedup_chunk = Some(dedup.value());
+            }
+
+            // options of a new file are recorded, as they cannot be read
+            // back from ZboxFS
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let mut inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref mut repo) => repo.path_exists(&path)
+                        .and_then(|exists| {
+                            let file = options.open(repo, &path)?;
+                            if !exists {
+                                let path = Path::new(&path);
+                                meta::save_file_options(repo, path, file_opts)?;
+                            }
+                            Ok(file)
+                        }),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|file| {
+                    let ptr_num = Box::into_raw(Box::new(file)) as i64;
+                    Ok(cx.number(ptr_num as f64).upcast())
+                })
+        }
+
+        method createDir(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let mut inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref mut repo) => repo.create_dir(&path),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|_| Ok(cx.undefined().upcast()))
+        }
+
+        method createDirAll(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let mut inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref mut repo) => repo.create_dir_all(&path),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|_| Ok(cx.undefined().upcast()))
+        }
+
+        method readDir(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref repo) => repo.read_dir(&path),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|dirs| {
+                    let js_array = cx.empty_array();
+                    let dirs = dirs
+                        .iter()
+                        .filter(|ent| !meta::is_meta_path(ent.path()));
+                    for (i, ent) in dirs.enumerate() {
+                        let js_ent = dir_entry_to_js_obj(&mut cx, ent);
+                        js_array.set(&mut cx, i as u32, js_ent).unwrap();
+                    }
+                    Ok(js_array.upcast())
+                })
+        }
+
+        method readDirPage(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let opts = cx.argument::<JsObject>(1)?;
+            let this = cx.this();
+
+            let mut options = PageOptions::default();
+            if let Ok(offset) = opts.get(&mut cx, "offset")?
+                .downcast::<JsNumber>()
+            {
+                options.offset = Some(offset.value() as usize);
+            }
+            if let Ok(limit) = opts.get(&mut cx, "limit")?
+                .downcast::<JsNumber>()
+            {
+                options.limit = Some(limit.value() as usize);
+            }
+            if let Ok(sort_by) = opts.get(&mut cx, "sortBy")?
+                .downcast::<JsString>()
+            {
+                match sort_by.value().parse::<SortBy>() {
+                    Ok(sort_by) => options.sort_by = sort_by,
+                    Err(err) => return cx.throw_error(error_string(err)),
+                }
+            }
+            if let Ok(order) = opts.get(&mut cx, "order")?
+                .downcast::<JsString>()
+            {
+                options.desc = match order.value().as_str() {
+                    "asc" => false,
+                    "desc" => true,
+                    _ => return cx.throw_error(
+                        error_string(Error::InvalidArgument)
+                    ),
+                };
+            }
+            if let Ok(cursor) = opts.get(&mut cx, "cursor")?
+                .downcast::<JsString>()
+            {
+                options.cursor = Some(cursor.value());
+            }
+            let with_metadata = opts
+                .get(&mut cx, "withMetadata")?
+                .downcast::<JsBoolean>()
+                .map(|val| val.value())
+                .unwrap_or(false);
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref repo) => {
+                        page::read_dir_page(repo, Path::new(&path), &options)
+                    }
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|page| {
+                    let js_array = cx.empty_array();
+                    for (i, ent) in page.entries.iter().enumerate() {
+                        let js_ent = if with_metadata {
+                            dir_entry_to_js_obj(&mut cx, ent)
+                        } else {
+                            let js_ent = cx.empty_object();
+                            let path = cx.string(ent.path().to_str().unwrap());
+                            js_ent.set(&mut cx, "path", path)?;
+                            let file_name = cx.string(ent.file_name());
+                            js_ent.set(&mut cx, "fileName", file_name)?;
+                            js_ent
+                        };
+                        js_array.set(&mut cx, i as u32, js_ent)?;
+                    }
+
+                    let js_page = cx.empty_object();
+                    js_page.set(&mut cx, "entries", js_array)?;
+                    let cursor = match page.cursor {
+                        Some(cursor) => cx.string(cursor).upcast::<JsValue>(),
+                        None => cx.null().upcast(),
+                    };
+                    js_page.set(&mut cx, "cursor", cursor)?;
+                    Ok(js_page.upcast())
+                })
+        }
+
+        method metadata(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref repo) => repo.metadata(&path),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|md| {
+                    let metadata = metadata_to_js_obj(&mut cx, md);
+                    Ok(metadata.upcast())
+                })
+        }
+
+        method history(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let mut inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref mut repo) => repo.history(&path).and_then(|hist| {
+                        let notes = notes::file_notes(repo, Path::new(&path))?;
+                        Ok((hist, notes))
+                    }),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|(hist, notes)| {
+                    let ret = hist_to_js_array(&mut cx, hist, &notes);
+                    Ok(ret.upcast())
+                })
+        }
+
+        method openVersion(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let ver_num = cx.argument::<JsNumber>(1)?.value() as usize;
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let mut inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref mut repo) => repo
+                        .open_file(&path)
+                        .and_then(|file| file.version_reader(ver_num))
+                        .map(|rdr| Box::into_raw(Box::new(rdr)) as i64),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|ptr_num| Ok(cx.number(ptr_num as f64).upcast()))
+        }
+
+        method openVersionAt(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let time = cx.argument::<JsNumber>(1)?.value();
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let mut inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref mut repo) => repo
+                        .history(&path)
+                        .and_then(|hist| version_at(&hist, time))
+                        .and_then(|ver_num| {
+                            repo.open_file(&path)?.version_reader(ver_num)
+                        })
+                        .map(|rdr| Box::into_raw(Box::new(rdr)) as i64),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|ptr_num| Ok(cx.number(ptr_num as f64).upcast()))
         }
 
-        method close(mut cx) {
+        method diffVersions(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let ver_a = cx.argument::<JsNumber>(1)?.value() as usize;
+            let ver_b = cx.argument::<JsNumber>(2)?.value() as usize;
             let this = cx.this();
-            {
+
+            let result = {
                 let guard = cx.lock();
                 let wrapper = this.borrow(&guard);
                 let mut inner = wrapper.0.lock().unwrap();
-                inner.take();
-            }
-            Ok(cx.undefined().upcast())
+                match *inner {
+                    Some(ref mut repo) => repo
+                        .open_file(&path)
+                        .and_then(|file| {
+                            let mut rdr_a = file.version_reader(ver_a)?;
+                            let mut rdr_b = file.version_reader(ver_b)?;
+                            diff::diff_versions(&path, &mut rdr_a, &mut rdr_b)
+                        }),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|diff| {
+                    let ret = version_diff_to_js_obj(&mut cx, diff);
+                    Ok(ret.upcast())
+                })
         }
 
-        method info(mut cx) {
+        method pruneHistory(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let opts = cx.argument::<JsObject>(1)?;
+            let policy = prune_policy_from_js(&mut cx, opts)?;
             let this = cx.this();
 
             let result = {
                 let guard = cx.lock();
-                let inner = this.borrow(&guard);
-                let wrapper = inner.0.lock().unwrap();
-                match *wrapper {
-                    Some(ref repo) => repo.info(),
-                    None => Err(Error::Closed),
+                let wrapper = this.borrow(&guard);
+                let mut inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref mut repo) => prune::prune_history(
+                        repo,
+                        Path::new(&path),
+                        &policy,
+                    ),
+                    None => Err(Error::RepoClosed),
                 }
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|info| {
-                    let info_obj = cx.empty_object();
-                    let val = cx.string(info.volume_id().to_string());
-                    info_obj.set(&mut cx, "volumeId", val)?;
-                    let val = cx.string(info.version());
-                    info_obj.set(&mut cx, "version", val)?;
-                    let val = cx.string(info.uri());
-                    info_obj.set(&mut cx, "uri", val)?;
-                    let val = cx.boolean(info.compress());
-                    info_obj.set(&mut cx, "compress", val)?;
-                    let val = cx.number(info.version_limit());
-                    info_obj.set(&mut cx, "versionLimit", val)?;
-                    let val = cx.boolean(info.dedup_chunk());
-                    info_obj.set(&mut cx, "dedupChunk", val)?;
-                    let val = cx.boolean(info.is_read_only());
-                    info_obj.set(&mut cx, "isReadOnly", val)?;
-                    let val = cx.number(time_to_f64(info.created_at()));
-                    info_obj.set(&mut cx, "createdAt", val)?;
-                    Ok(info_obj.upcast())
+                .and_then(|report| {
+                    let ret = prune_report_to_js_obj(&mut cx, report);
+                    Ok(ret.upcast())
                 })
         }
 
-        method resetPassword(mut cx) {
-            let old_pwd = cx.argument::<JsString>(0)?.value();
-            let new_pwd = cx.argument::<JsString>(1)?.value();
-            let ops_limit =
-                OpsLimit::from(cx.argument::<JsNumber>(2)?.value() as i32);
-            let mem_limit =
-                MemLimit::from(cx.argument::<JsNumber>(3)?.value() as i32);
+        method pruneAllHistory(mut cx) {
+            let opts = cx.argument::<JsObject>(0)?;
+            let policy = prune_policy_from_js(&mut cx, opts)?;
             let this = cx.this();
 
             let result = {
@@ -237,72 +1766,100 @@ declare_types! {
                 let wrapper = this.borrow(&guard);
                 let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref mut repo) => repo.reset_password(&old_pwd,
-                        &new_pwd, ops_limit, mem_limit),
+                    Some(ref mut repo) => {
+                        prune::prune_all_history(repo, &policy)
+                    }
                     None => Err(Error::RepoClosed),
                 }
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|_| Ok(cx.undefined().upcast()))
+                .and_then(|report| {
+                    let ret = prune_report_to_js_obj(&mut cx, report);
+                    Ok(ret.upcast())
+                })
         }
 
-        method pathExists(mut cx) {
+        method annotateVersion(mut cx) {
             let path = cx.argument::<JsString>(0)?.value();
+            let ver_num = cx.argument::<JsNumber>(1)?.value() as usize;
+            let opts = cx.argument::<JsObject>(2)?;
+            let message = opts
+                .get(&mut cx, "message")?
+                .downcast::<JsString>()
+                .ok()
+                .map(|val| val.value());
+            let author = opts
+                .get(&mut cx, "author")?
+                .downcast::<JsString>()
+                .ok()
+                .map(|val| val.value());
             let this = cx.this();
 
             let result = {
                 let guard = cx.lock();
                 let wrapper = this.borrow(&guard);
-                let inner = wrapper.0.lock().unwrap();
+                let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref repo) => repo.path_exists(&path),
+                    Some(ref mut repo) => notes::annotate(
+                        repo,
+                        Path::new(&path),
+                        ver_num,
+                        message,
+                        author,
+                    ),
                     None => Err(Error::RepoClosed),
                 }
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|result| Ok(cx.boolean(result).upcast()))
+                .and_then(|_| Ok(cx.undefined().upcast()))
         }
 
-        method isFile(mut cx) {
+        method labelVersion(mut cx) {
             let path = cx.argument::<JsString>(0)?.value();
+            let ver_num = cx.argument::<JsNumber>(1)?.value() as usize;
+            let label = cx.argument::<JsString>(2)?.value();
             let this = cx.this();
 
             let result = {
                 let guard = cx.lock();
                 let wrapper = this.borrow(&guard);
-                let inner = wrapper.0.lock().unwrap();
+                let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref repo) => repo.is_file(&path),
+                    Some(ref mut repo) => {
+                        notes::label(repo, Path::new(&path), ver_num, label)
+                    }
                     None => Err(Error::RepoClosed),
                 }
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|result| Ok(cx.boolean(result).upcast()))
+                .and_then(|_| Ok(cx.undefined().upcast()))
         }
 
-        method isDir(mut cx) {
-            let path = cx.argument::<JsString>(0)?.value();
+        method createSnapshot(mut cx) {
+            let name = cx.argument::<JsString>(0)?.value();
             let this = cx.this();
 
             let result = {
                 let guard = cx.lock();
                 let wrapper = this.borrow(&guard);
-                let inner = wrapper.0.lock().unwrap();
+                let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref repo) => repo.is_dir(&path),
+                    Some(ref mut repo) => snapshot::create(repo, &name),
                     None => Err(Error::RepoClosed),
                 }
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|result| Ok(cx.boolean(result).upcast()))
+                .and_then(|snapshot| {
+                    let ret = snapshot_to_js_obj(&mut cx, &snapshot, false);
+                    Ok(ret.upcast())
+                })
         }
 
-        method createFile(mut cx) {
-            let path = cx.argument::<JsString>(0)?.value();
+        method listSnapshots(mut cx) {
             let this = cx.this();
 
             let result = {
@@ -310,84 +1867,46 @@ declare_types! {
                 let wrapper = this.borrow(&guard);
                 let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref mut repo) => repo.create_file(&path),
+                    Some(ref mut repo) => snapshot::list(repo),
                     None => Err(Error::RepoClosed),
                 }
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|file| {
-                    let ptr_num = Box::into_raw(Box::new(file)) as i64;
-                    Ok(cx.number(ptr_num as f64).upcast())
+                .and_then(|snapshots| {
+                    let js_array = cx.empty_array();
+                    for (i, snapshot) in snapshots.iter().enumerate() {
+                        let js_snapshot =
+                            snapshot_to_js_obj(&mut cx, snapshot, false);
+                        js_array.set(&mut cx, i as u32, js_snapshot)?;
+                    }
+                    Ok(js_array.upcast())
                 })
         }
 
-        method openFile(mut cx) {
-            let path = cx.argument::<JsString>(0)?.value();
-            let opts = cx.argument::<JsObject>(1)?;
+        method openSnapshot(mut cx) {
+            let name = cx.argument::<JsString>(0)?.value();
             let this = cx.this();
 
-            let mut options = OpenOptions::new();
-            if let Ok(read) = opts.get(&mut cx, "read")?
-                .downcast::<JsBoolean>()
-            {
-                options.read(read.value());
-            }
-            if let Ok(write) = opts.get(&mut cx, "write")?
-                .downcast::<JsBoolean>()
-            {
-                options.write(write.value());
-            }
-            if let Ok(append) = opts.get(&mut cx, "append")?
-                .downcast::<JsBoolean>()
-            {
-                options.append(append.value());
-            }
-            if let Ok(truncate) = opts.get(&mut cx, "truncate")?
-                .downcast::<JsBoolean>()
-            {
-                options.truncate(truncate.value());
-            }
-            if let Ok(create) = opts.get(&mut cx, "create")?
-                .downcast::<JsBoolean>()
-            {
-                options.create(create.value());
-            }
-            if let Ok(create_new) = opts.get(&mut cx, "createNew")?
-                .downcast::<JsBoolean>()
-            {
-                options.create_new(create_new.value());
-            }
-            if let Ok(limit) = opts.get(&mut cx, "versionLimit")?
-                .downcast::<JsNumber>()
-            {
-                options.version_limit(limit.value() as u8);
-            }
-            if let Ok(dedup) = opts.get(&mut cx, "dedupChunk")?
-                .downcast::<JsBoolean>()
-            {
-                options.dedup_chunk(dedup.value());
-            }
-
             let result = {
                 let guard = cx.lock();
                 let wrapper = this.borrow(&guard);
                 let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref mut repo) => options.open(repo, &path),
+                    Some(ref mut repo) => snapshot::open(repo, &name),
                     None => Err(Error::RepoClosed),
                 }
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|file| {
-                    let ptr_num = Box::into_raw(Box::new(file)) as i64;
-                    Ok(cx.number(ptr_num as f64).upcast())
+                .and_then(|snapshot| {
+                    let ret = snapshot_to_js_obj(&mut cx, &snapshot, true);
+                    Ok(ret.upcast())
                 })
         }
 
-        method createDir(mut cx) {
-            let path = cx.argument::<JsString>(0)?.value();
+        method restoreSnapshot(mut cx) {
+            let name = cx.argument::<JsString>(0)?.value();
             let this = cx.this();
 
             let result = {
@@ -395,17 +1914,50 @@ declare_types! {
                 let wrapper = this.borrow(&guard);
                 let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref mut repo) => repo.create_dir(&path),
+                    Some(ref mut repo) => snapshot::restore(repo, &name),
                     None => Err(Error::RepoClosed),
                 }
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|_| Ok(cx.undefined().upcast()))
+                .and_then(|report| {
+                    let ret = restore_report_to_js_obj(&mut cx, report);
+                    Ok(ret.upcast())
+                })
         }
 
-        method createDirAll(mut cx) {
-            let path = cx.argument::<JsString>(0)?.value();
+        method glob(mut cx) {
+            let pattern = cx.argument::<JsString>(0)?.value();
+            let ignore = cx.argument::<JsArray>(1)?;
+            let ignore = strings_from_js(&mut cx, ignore)?;
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref repo) => Glob::new(&pattern, &ignore)
+                        .and_then(|glob| glob.find(repo)),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|ents| {
+                    let js_array = cx.empty_array();
+                    for (i, ent) in ents.iter().enumerate() {
+                        let js_ent = dir_entry_to_js_obj(&mut cx, ent);
+                        js_array.set(&mut cx, i as u32, js_ent)?;
+                    }
+                    Ok(js_array.upcast())
+                })
+        }
+
+        method removeGlob(mut cx) {
+            let pattern = cx.argument::<JsString>(0)?.value();
+            let ignore = cx.argument::<JsArray>(1)?;
+            let ignore = strings_from_js(&mut cx, ignore)?;
             let this = cx.this();
 
             let result = {
@@ -413,17 +1965,26 @@ declare_types! {
                 let wrapper = this.borrow(&guard);
                 let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref mut repo) => repo.create_dir_all(&path),
+                    Some(ref mut repo) => Glob::new(&pattern, &ignore)
+                        .and_then(|glob| glob::remove(repo, &glob)),
                     None => Err(Error::RepoClosed),
                 }
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|_| Ok(cx.undefined().upcast()))
+                .and_then(|paths| {
+                    let js_array = cx.empty_array();
+                    for (i, path) in paths.iter().enumerate() {
+                        let val = cx.string(path);
+                        js_array.set(&mut cx, i as u32, val)?;
+                    }
+                    Ok(js_array.upcast())
+                })
         }
 
-        method readDir(mut cx) {
+        method du(mut cx) {
             let path = cx.argument::<JsString>(0)?.value();
+            let top = cx.argument::<JsNumber>(1)?.value() as usize;
             let this = cx.this();
 
             let result = {
@@ -431,72 +1992,381 @@ declare_types! {
                 let wrapper = this.borrow(&guard);
                 let inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref repo) => repo.read_dir(&path),
+                    Some(ref repo) => {
+                        du::disk_usage(repo, Path::new(&path), top)
+                    }
                     None => Err(Error::RepoClosed),
                 }
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|dirs| {
-                    let js_array = cx.empty_array();
-                    for (i, ent) in dirs.iter().enumerate() {
-                        let js_ent = cx.empty_object();
+                .and_then(|du| {
+                    let ret = disk_usage_to_js_obj(&mut cx, &du);
+                    Ok(ret.upcast())
+                })
+        }
 
-                        let path = cx.string(ent.path().to_str().unwrap().to_owned());
-                        let file_name = cx.string(ent.file_name().to_owned());
-                        js_ent.set(&mut cx, "path", path).unwrap();
-                        js_ent.set(&mut cx, "fileName", file_name).unwrap();
-                        let md = metadata_to_js_obj(&mut cx, ent.metadata());
-                        js_ent.set(&mut cx, "metadata", md).unwrap();
+        method changesSince(mut cx) {
+            let time = cx.argument::<JsNumber>(0)?.value();
+            let path = cx.argument::<JsString>(1)?.value();
+            let this = cx.this();
 
-                        js_array.set(&mut cx, i as u32, js_ent).unwrap();
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let mut inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref mut repo) => {
+                        changes::changes_since(repo, Path::new(&path), time)
+                    }
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|changes| {
+                    let js_array = cx.empty_array();
+                    for (i, change) in changes.iter().enumerate() {
+                        let js_change = change_to_js_obj(&mut cx, change);
+                        js_array.set(&mut cx, i as u32, js_change)?;
                     }
                     Ok(js_array.upcast())
                 })
         }
 
-        method metadata(mut cx) {
+        method copyDirAll(mut cx) {
+            let from = cx.argument::<JsString>(0)?.value();
+            let to = cx.argument::<JsString>(1)?.value();
+            let opts = cx.argument::<JsObject>(2)?;
+            let callback = cx
+                .argument_opt(3)
+                .and_then(|arg| arg.downcast::<JsFunction>().ok());
+            let this = cx.this();
+
+            let mut options = CopyOptions::default();
+            if let Ok(overwrite) = opts.get(&mut cx, "overwrite")?
+                .downcast::<JsBoolean>()
+            {
+                options.overwrite = overwrite.value();
+            }
+            if let Ok(preserve) = opts.get(&mut cx, "preserveHistory")?
+                .downcast::<JsBoolean>()
+            {
+                options.preserve_history = preserve.value();
+            }
+
+            let wrapper = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                wrapper.clone()
+            };
+            let mut thrown = false;
+            let result = lend_repo(&wrapper, |repo| {
+                transfer::copy_dir_all(
+                    repo,
+                    Path::new(&from),
+                    Path::new(&to),
+                    &options,
+                    &mut |progress| {
+                        call_progress(
+                            &mut cx,
+                            callback,
+                            progress,
+                            &mut thrown,
+                        )
+                    },
+                )
+            })
+            .unwrap_or_else(|| {
+                Err(TransferError {
+                    err: Error::RepoClosed,
+                    path: from.clone(),
+                    done: 0,
+                })
+            });
+            match result {
+                Ok(copied) => Ok(cx.number(copied as f64).upcast()),
+                Err(_) if thrown => Err(Throw),
+                Err(err) => throw_transfer_error(&mut cx, err),
+            }
+        }
+
+        method move(mut cx) {
+            let from = cx.argument::<JsString>(0)?.value();
+            let to = cx.argument::<JsString>(1)?.value();
+            let overwrite = cx.argument::<JsBoolean>(2)?.value();
+            let callback = cx
+                .argument_opt(3)
+                .and_then(|arg| arg.downcast::<JsFunction>().ok());
+            let this = cx.this();
+
+            let wrapper = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                wrapper.clone()
+            };
+            let mut thrown = false;
+            let result = lend_repo(&wrapper, |repo| {
+                transfer::move_path(
+                    repo,
+                    Path::new(&from),
+                    Path::new(&to),
+                    overwrite,
+                    &mut |progress| {
+                        call_progress(
+                            &mut cx,
+                            callback,
+                            progress,
+                            &mut thrown,
+                        )
+                    },
+                )
+            })
+            .unwrap_or_else(|| {
+                Err(TransferError {
+                    err: Error::RepoClosed,
+                    path: from.clone(),
+                    done: 0,
+                })
+            });
+            match result {
+                Ok(moved) => Ok(cx.number(moved as f64).upcast()),
+                Err(_) if thrown => Err(Throw),
+                Err(err) => throw_transfer_error(&mut cx, err),
+            }
+        }
+
+        method importFrom(mut cx) {
+            let host_path = cx.argument::<JsString>(0)?.value();
+            let path = cx.argument::<JsString>(1)?.value();
+            let overwrite = cx.argument::<JsBoolean>(2)?.value();
+            let filter = cx
+                .argument_opt(3)
+                .and_then(|arg| arg.downcast::<JsFunction>().ok());
+            let progress = cx
+                .argument_opt(4)
+                .and_then(|arg| arg.downcast::<JsFunction>().ok());
+            let this = cx.this();
+
+            let wrapper = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                wrapper.clone()
+            };
+            let mut hooks = JsImportHooks {
+                cx: &mut cx,
+                filter,
+                progress,
+                thrown: false,
+            };
+            let result = lend_repo(&wrapper, |repo| {
+                host::import_from(
+                    repo,
+                    Path::new(&host_path),
+                    Path::new(&path),
+                    overwrite,
+                    &mut hooks,
+                )
+            })
+            .unwrap_or_else(|| {
+                Err(TransferError {
+                    err: Error::RepoClosed,
+                    path: path.clone(),
+                    done: 0,
+                })
+            });
+            let thrown = hooks.thrown;
+            match result {
+                Ok(report) => {
+                    let ret = import_report_to_js_obj(&mut cx, &report);
+                    Ok(ret.upcast())
+                }
+                Err(_) if thrown => Err(Throw),
+                Err(err) => throw_transfer_error(&mut cx, err),
+            }
+        }
+
+        method exportTo(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let host_path = cx.argument::<JsString>(1)?.value();
+            let overwrite = cx.argument::<JsBoolean>(2)?.value();
+            let time = cx
+                .argument_opt(3)
+                .and_then(|arg| arg.downcast::<JsNumber>().ok())
+                .map(|arg| arg.value());
+            let callback = cx
+                .argument_opt(4)
+                .and_then(|arg| arg.downcast::<JsFunction>().ok());
+            let this = cx.this();
+
+            let wrapper = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                wrapper.clone()
+            };
+            let mut thrown = false;
+            let result = lend_repo(&wrapper, |repo| {
+                host::export_to(
+                    repo,
+                    Path::new(&path),
+                    Path::new(&host_path),
+                    time,
+                    overwrite,
+                    &mut |progress| {
+                        call_progress(
+                            &mut cx,
+                            callback,
+                            progress,
+                            &mut thrown,
+                        )
+                    },
+                )
+            })
+            .unwrap_or_else(|| {
+                Err(TransferError {
+                    err: Error::RepoClosed,
+                    path: path.clone(),
+                    done: 0,
+                })
+            });
+            match result {
+                Ok(report) => {
+                    let ret = export_report_to_js_obj(&mut cx, &report);
+                    Ok(ret.upcast())
+                }
+                Err(_) if thrown => Err(Throw),
+                Err(err) => throw_transfer_error(&mut cx, err),
+            }
+        }
+
+        method exportZip(mut cx) {
             let path = cx.argument::<JsString>(0)?.value();
+            let opts = cx.argument::<JsObject>(1)?;
             let this = cx.this();
 
+            let mut options = ZipOptions::default();
+            if let Ok(level) = opts.get(&mut cx, "compressionLevel")?
+                .downcast::<JsNumber>()
+            {
+                options.compression_level = Some(level.value() as i64);
+            }
+            if let Ok(pwd) = opts.get(&mut cx, "password")?
+                .downcast::<JsString>()
+            {
+                options.password = Some(pwd.value());
+            }
+
             let result = {
                 let guard = cx.lock();
                 let wrapper = this.borrow(&guard);
-                let inner = wrapper.0.lock().unwrap();
+                let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref repo) => repo.metadata(&path),
+                    Some(ref mut repo) => {
+                        zip::export_zip(repo, Path::new(&path), &options)
+                    }
                     None => Err(Error::RepoClosed),
                 }
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|md| {
-                    let metadata = metadata_to_js_obj(&mut cx, md);
-                    Ok(metadata.upcast())
+                .and_then(|buf| {
+                    let ret = cx.array_buffer(buf.len() as u32)?;
+                    cx.borrow(&ret, |buf_data| {
+                        let slice = buf_data.as_mut_slice::<u8>();
+                        slice.copy_from_slice(&buf);
+                    });
+                    Ok(ret.upcast())
                 })
         }
 
-        method history(mut cx) {
-            let path = cx.argument::<JsString>(0)?.value();
+        method importZip(mut cx) {
+            let buf = cx.argument::<JsArrayBuffer>(0)?;
+            let buf_offset = cx.argument::<JsNumber>(1)?.value() as usize;
+            let buf_len = cx.argument::<JsNumber>(2)?.value() as usize;
+            let dest = cx.argument::<JsString>(3)?.value();
+            let pwd = cx
+                .argument_opt(4)
+                .and_then(|arg| arg.downcast::<JsString>().ok())
+                .map(|arg| arg.value());
             let this = cx.this();
 
             let result = {
                 let guard = cx.lock();
                 let wrapper = this.borrow(&guard);
-                let inner = wrapper.0.lock().unwrap();
-                match *inner {
-                    Some(ref repo) => repo.history(&path),
-                    None => Err(Error::RepoClosed),
-                }
+                let mut inner = wrapper.0.lock().unwrap();
+                cx.borrow(&buf, |data| {
+                    let slice = data.as_slice::<u8>();
+                    let data = &slice[buf_offset..buf_offset + buf_len];
+                    match *inner {
+                        Some(ref mut repo) => zip::import_zip(
+                            repo,
+                            data,
+                            Path::new(&dest),
+                            pwd.as_ref().map(|pwd| pwd.as_str()),
+                        ),
+                        None => Err(Error::RepoClosed),
+                    }
+                })
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|hist| {
-                    let ret = hist_to_js_array(&mut cx, hist);
+                .and_then(|report| {
+                    let ret = unpack_report_to_js_obj(&mut cx, &report);
                     Ok(ret.upcast())
                 })
         }
 
+        method verify(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let all_versions = cx.argument::<JsBoolean>(1)?.value();
+            let callback = cx
+                .argument_opt(2)
+                .and_then(|arg| arg.downcast::<JsFunction>().ok());
+            let this = cx.this();
+
+            let wrapper = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                wrapper.clone()
+            };
+            let mut thrown = false;
+            let result = lend_repo(&wrapper, |repo| {
+                verify::verify(
+                    repo,
+                    Path::new(&path),
+                    all_versions,
+                    &mut |progress| {
+                        call_progress(&mut cx, callback, progress, &mut thrown)
+                    },
+                )
+            })
+            .unwrap_or(Err(Error::RepoClosed));
+            match result {
+                Ok(report) => {
+                    Ok(verify_report_to_js_obj(&mut cx, report).upcast())
+                }
+                Err(_) if thrown => Err(Throw),
+                Err(err) => cx.throw_error(error_string(err)),
+            }
+        }
+
+        method hashFile(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let arg = cx.argument_opt(1);
+            let algo = hash_algo_from_js(&mut cx, arg)?;
+            let callback = cx.argument::<JsFunction>(2)?;
+            let this = cx.this();
+
+            let repo = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                wrapper.0.clone()
+            };
+            let source = HashSource::Path(repo, PathBuf::from(path));
+            HashTask { source, algo }.schedule(callback);
+            Ok(cx.undefined().upcast())
+        }
+
         method copy(mut cx) {
             let from = cx.argument::<JsString>(0)?.value();
             let to = cx.argument::<JsString>(1)?.value();
@@ -507,7 +2377,15 @@ declare_types! {
                 let wrapper = this.borrow(&guard);
                 let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref mut repo) => repo.copy(&from, &to),
+                    Some(ref mut repo) => repo.path_exists(&to)
+                        .and_then(|exists| {
+                            repo.copy(&from, &to)?;
+                            if exists {
+                                return Ok(());
+                            }
+                            let (from, to) = (Path::new(&from), Path::new(&to));
+                            meta::copy_file_options(repo, from, to)
+                        }),
                     None => Err(Error::RepoClosed),
                 }
             };
@@ -525,7 +2403,9 @@ declare_types! {
                 let wrapper = this.borrow(&guard);
                 let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref mut repo) => repo.remove_file(&path),
+                    Some(ref mut repo) => repo.remove_file(&path).map(|_| {
+                        changes::record_removal(repo, &path, false, None)
+                    }),
                     None => Err(Error::RepoClosed),
                 }
             };
@@ -543,7 +2423,9 @@ declare_types! {
                 let wrapper = this.borrow(&guard);
                 let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref mut repo) => repo.remove_dir(&path),
+                    Some(ref mut repo) => repo.remove_dir(&path).map(|_| {
+                        changes::record_removal(repo, &path, true, None)
+                    }),
                     None => Err(Error::RepoClosed),
                 }
             };
@@ -561,7 +2443,11 @@ declare_types! {
                 let wrapper = this.borrow(&guard);
                 let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref mut repo) => repo.remove_dir_all(&path),
+                    Some(ref mut repo) => {
+                        repo.remove_dir_all(&path).map(|_| {
+                            changes::record_removal(repo, &path, true, None)
+                        })
+                    }
                     None => Err(Error::RepoClosed),
                 }
             };
@@ -580,7 +2466,7 @@ declare_types! {
                 let wrapper = this.borrow(&guard);
                 let mut inner = wrapper.0.lock().unwrap();
                 match *inner {
-                    Some(ref mut repo) => repo.rename(&from, &to),
+                    Some(ref mut repo) => rename(repo, &from, &to),
                     None => Err(Error::RepoClosed),
                 }
             };
@@ -713,7 +2599,9 @@ declare_types! {
                     let slice = data.as_slice::<u8>();
                     match *inner.0.lock().unwrap() {
                         Some(ref mut file) => file
-                            .write_once(&slice[buf_offset..buf_offset + buf_len])
+                            .write_once(
+                                &slice[buf_offset..buf_offset + buf_len],
+                            )
                             .map_err(Error::from),
                         None => Err(Error::Closed),
                     }
@@ -785,6 +2673,22 @@ declare_types! {
                 .and_then(|ver| Ok(cx.number(ver as f64).upcast()))
         }
 
+        method hash(mut cx) {
+            let arg = cx.argument_opt(0);
+            let algo = hash_algo_from_js(&mut cx, arg)?;
+            let callback = cx.argument::<JsFunction>(1)?;
+            let this = cx.this();
+
+            let file = {
+                let guard = cx.lock();
+                let inner = this.borrow(&guard);
+                inner.0.clone()
+            };
+            let source = HashSource::File(file);
+            HashTask { source, algo }.schedule(callback);
+            Ok(cx.undefined().upcast())
+        }
+
         method versionReader(mut cx) {
             let ver_num = cx.argument::<JsNumber>(0)?.value() as usize;
             let this = cx.this();
@@ -826,21 +2730,46 @@ declare_types! {
         }
 
         method history(mut cx) {
+            // repo and path are optional, used to look up version notes
+            let repo = cx
+                .argument_opt(0)
+                .and_then(|arg| arg.downcast::<JsRepo>().ok());
+            let path = cx
+                .argument_opt(1)
+                .and_then(|arg| arg.downcast::<JsString>().ok())
+                .map(|arg| arg.value());
             let this = cx.this();
 
+            // file lock is released before taking repo lock, as other
+            // paths take repo lock first
             let result = {
                 let guard = cx.lock();
-                let inner = this.borrow(&guard);
-                let mut wrapper = inner.0.lock().unwrap();
-                match *wrapper {
-                    Some(ref file) => file.history(),
-                    None => Err(Error::Closed),
-                }
+                let hist = {
+                    let inner = this.borrow(&guard);
+                    let wrapper = inner.0.lock().unwrap();
+                    match *wrapper {
+                        Some(ref file) => file.history(),
+                        None => Err(Error::Closed),
+                    }
+                };
+                hist.and_then(|hist| match (repo, path) {
+                    (Some(repo), Some(path)) => {
+                        let repo_wrapper = repo.borrow(&guard);
+                        let mut repo_inner = repo_wrapper.0.lock().unwrap();
+                        match *repo_inner {
+                            Some(ref mut repo) => {
+                                notes::file_notes(repo, Path::new(&path))
+                            }
+                            None => Err(Error::RepoClosed),
+                        }.map(|notes| (hist, notes))
+                    }
+                    _ => Ok((hist, FileNotes::new())),
+                })
             };
             result
                 .or_else(|err| cx.throw_error(error_string(err)))
-                .and_then(|hist| {
-                    let ret = hist_to_js_array(&mut cx, hist);
+                .and_then(|(hist, notes)| {
+                    let ret = hist_to_js_array(&mut cx, hist, &notes);
                     Ok(ret.upcast())
                 })
         }
@@ -915,6 +2844,34 @@ declare_types! {
                 })
         }
 
+        method readChunk(mut cx) {
+            let chunk_size = cx.argument::<JsNumber>(0)?.value();
+            let chunk_size = to_chunk_size(chunk_size)
+                .or_else(|err| cx.throw_error(error_string(err)))?;
+            let this = cx.this();
+            let mut buf = vec![0u8; chunk_size];
+
+            let result = {
+                let guard = cx.lock();
+                let wrapper = this.borrow(&guard);
+                let mut inner = wrapper.0.lock().unwrap();
+                match *inner {
+                    Some(ref mut vrdr) => read_full(vrdr, &mut buf),
+                    None => Err(Error::Closed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|read| {
+                    let ret = cx.array_buffer(read as u32)?;
+                    cx.borrow(&ret, |buf_data| {
+                        let slice = buf_data.as_mut_slice::<u8>();
+                        slice.copy_from_slice(&buf[..read]);
+                    });
+                    Ok(ret.upcast())
+                })
+        }
+
         method seek(mut cx) {
             let from = cx.argument::<JsNumber>(0)?.value() as u32;
             let offset = cx.argument::<JsNumber>(1)?.value();
@@ -940,6 +2897,290 @@ declare_types! {
                 .or_else(|err| cx.throw_error(error_string(err)))
                 .and_then(|new_pos| Ok(cx.number(new_pos as f64).upcast()))
         }
+
+        method version(mut cx) {
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let inner = this.borrow(&guard);
+                let wrapper = inner.0.lock().unwrap();
+                match *wrapper {
+                    Some(ref vrdr) => vrdr.version(),
+                    None => Err(Error::Closed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|ver| {
+                    let ret = version_to_js_obj(&mut cx, &ver);
+                    Ok(ret.upcast())
+                })
+        }
+
+        method hash(mut cx) {
+            let arg = cx.argument_opt(0);
+            let algo = hash_algo_from_js(&mut cx, arg)?;
+            let callback = cx.argument::<JsFunction>(1)?;
+            let this = cx.this();
+
+            let vrdr = {
+                let guard = cx.lock();
+                let inner = this.borrow(&guard);
+                inner.0.clone()
+            };
+            let source = HashSource::Version(vrdr);
+            HashTask { source, algo }.schedule(callback);
+            Ok(cx.undefined().upcast())
+        }
+    }
+
+    pub class JsWalker for WalkerWrapper {
+        init(mut cx) {
+            let repo = cx.argument::<JsRepo>(0)?;
+            let path = cx.argument::<JsString>(1)?.value();
+            let opts = cx.argument::<JsObject>(2)?;
+
+            let mut options = WalkOptions::default();
+            if let Ok(max_depth) = opts.get(&mut cx, "maxDepth")?
+                .downcast::<JsNumber>()
+            {
+                options.max_depth = Some(max_depth.value() as usize);
+            }
+            if let Ok(files_only) = opts.get(&mut cx, "filesOnly")?
+                .downcast::<JsBoolean>()
+            {
+                options.files_only = files_only.value();
+            }
+            if let Ok(dirs_only) = opts.get(&mut cx, "dirsOnly")?
+                .downcast::<JsBoolean>()
+            {
+                options.dirs_only = dirs_only.value();
+            }
+            if let Ok(order) = opts.get(&mut cx, "followOrder")?
+                .downcast::<JsString>()
+            {
+                options.order = match order.value().as_str() {
+                    "pre" => WalkOrder::Pre,
+                    "post" => WalkOrder::Post,
+                    _ => return cx.throw_error(
+                        error_string(Error::InvalidArgument)
+                    ),
+                };
+            }
+
+            let repo = {
+                let guard = cx.lock();
+                let wrapper = repo.borrow(&guard);
+                wrapper.0.clone()
+            };
+            match Walker::new(Path::new(&path), options) {
+                Ok(walker) => Ok(WalkerWrapper {
+                    repo,
+                    walker: Arc::new(Mutex::new(Some(Box::new(walker)))),
+                }),
+                Err(err) => cx.throw_error(error_string(err)),
+            }
+        }
+
+        method close(mut cx) {
+            let this = cx.this();
+            {
+                let guard = cx.lock();
+                let inner = this.borrow(&guard);
+                let mut wrapper = inner.walker.lock().unwrap();
+                wrapper.take();
+            }
+            Ok(cx.undefined().upcast())
+        }
+
+        method next(mut cx) {
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let inner = this.borrow(&guard);
+                let mut walker = inner.walker.lock().unwrap();
+                let repo = inner.repo.lock().unwrap();
+                match (&mut *walker, &*repo) {
+                    (Some(ref mut walker), Some(ref repo)) => walker.next(repo),
+                    (None, _) => Ok(None),
+                    (_, None) => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|next| match next {
+                    Some((ent, depth)) => {
+                        let js_ent = dir_entry_to_js_obj(&mut cx, &ent);
+                        let val = cx.number(depth as f64);
+                        js_ent.set(&mut cx, "depth", val)?;
+                        Ok(js_ent.upcast())
+                    }
+                    None => Ok(cx.undefined().upcast()),
+                })
+        }
+    }
+
+    pub class JsTarReader for TarReaderWrapper {
+        init(mut cx) {
+            let repo = cx.argument::<JsRepo>(0)?;
+            let path = cx.argument::<JsString>(1)?.value();
+
+            let repo = {
+                let guard = cx.lock();
+                let wrapper = repo.borrow(&guard);
+                wrapper.0.clone()
+            };
+            let result = {
+                let inner = repo.lock().unwrap();
+                match *inner {
+                    Some(ref repo) => TarExporter::new(repo, Path::new(&path)),
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            match result {
+                Ok(exporter) => Ok(TarReaderWrapper {
+                    repo,
+                    exporter: Arc::new(Mutex::new(Some(Box::new(exporter)))),
+                }),
+                Err(err) => cx.throw_error(error_string(err)),
+            }
+        }
+
+        method close(mut cx) {
+            let this = cx.this();
+            {
+                let guard = cx.lock();
+                let inner = this.borrow(&guard);
+                let mut wrapper = inner.exporter.lock().unwrap();
+                wrapper.take();
+            }
+            Ok(cx.undefined().upcast())
+        }
+
+        method readChunk(mut cx) {
+            let size = cx.argument::<JsNumber>(0)?.value();
+            let size = to_chunk_size(size)
+                .or_else(|err| cx.throw_error(error_string(err)))?;
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let inner = this.borrow(&guard);
+                let mut exporter = inner.exporter.lock().unwrap();
+                let mut repo = inner.repo.lock().unwrap();
+                match (&mut *exporter, &mut *repo) {
+                    (Some(ref mut exporter), Some(ref mut repo)) => {
+                        exporter.read_chunk(repo, size)
+                    }
+                    (None, _) => Err(Error::Closed),
+                    (_, None) => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|buf| {
+                    let ret = cx.array_buffer(buf.len() as u32)?;
+                    cx.borrow(&ret, |buf_data| {
+                        let slice = buf_data.as_mut_slice::<u8>();
+                        slice.copy_from_slice(&buf);
+                    });
+                    Ok(ret.upcast())
+                })
+        }
+    }
+
+    pub class JsTarWriter for TarWriterWrapper {
+        init(mut cx) {
+            let repo = cx.argument::<JsRepo>(0)?;
+            let dest = cx.argument::<JsString>(1)?.value();
+
+            let repo = {
+                let guard = cx.lock();
+                let wrapper = repo.borrow(&guard);
+                wrapper.0.clone()
+            };
+            let result = {
+                let mut inner = repo.lock().unwrap();
+                match *inner {
+                    Some(ref mut repo) => {
+                        TarImporter::new(repo, Path::new(&dest))
+                    }
+                    None => Err(Error::RepoClosed),
+                }
+            };
+            match result {
+                Ok(importer) => Ok(TarWriterWrapper {
+                    repo,
+                    importer: Arc::new(Mutex::new(Some(Box::new(importer)))),
+                }),
+                Err(err) => cx.throw_error(error_string(err)),
+            }
+        }
+
+        method close(mut cx) {
+            let this = cx.this();
+            {
+                let guard = cx.lock();
+                let inner = this.borrow(&guard);
+                let mut wrapper = inner.importer.lock().unwrap();
+                wrapper.take();
+            }
+            Ok(cx.undefined().upcast())
+        }
+
+        method write(mut cx) {
+            let buf = cx.argument::<JsArrayBuffer>(0)?;
+            let buf_offset = cx.argument::<JsNumber>(1)?.value() as usize;
+            let buf_len = cx.argument::<JsNumber>(2)?.value() as usize;
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let inner = this.borrow(&guard);
+                let mut importer = inner.importer.lock().unwrap();
+                let mut repo = inner.repo.lock().unwrap();
+                cx.borrow(&buf, |data| {
+                    let slice = data.as_slice::<u8>();
+                    let chunk = &slice[buf_offset..buf_offset + buf_len];
+                    match (&mut *importer, &mut *repo) {
+                        (Some(ref mut importer), Some(ref mut repo)) => {
+                            importer.write(repo, chunk)
+                        }
+                        (None, _) => Err(Error::Closed),
+                        (_, None) => Err(Error::RepoClosed),
+                    }
+                })
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|_| Ok(cx.undefined().upcast()))
+        }
+
+        method finish(mut cx) {
+            let this = cx.this();
+
+            let result = {
+                let guard = cx.lock();
+                let inner = this.borrow(&guard);
+                let importer = inner.importer.lock().unwrap().take();
+                let mut repo = inner.repo.lock().unwrap();
+                match (importer, &mut *repo) {
+                    (Some(importer), Some(ref mut repo)) => {
+                        importer.finish(repo)
+                    }
+                    (None, _) => Err(Error::Closed),
+                    (_, None) => Err(Error::RepoClosed),
+                }
+            };
+            result
+                .or_else(|err| cx.throw_error(error_string(err)))
+                .and_then(|report| {
+                    let ret = unpack_report_to_js_obj(&mut cx, &report);
+                    Ok(ret.upcast())
+                })
+        }
     }
 }
 
@@ -949,8 +3190,16 @@ register_module!(mut cx, {
     cx.export_function("openRepo", open_repo)?;
     cx.export_function("repoExists", repo_exists)?;
     cx.export_function("repairSuperBlock", repair_super_block)?;
+    cx.export_function("checkSuperBlock", check_super_block)?;
+    cx.export_function("verifyPassword", verify_password)?;
+    cx.export_function("migrateRepo", migrate_repo)?;
+    cx.export_function("mirror", mirror_repo)?;
+    cx.export_function("diffTrees", diff_trees)?;
     cx.export_class::<JsRepo>("Repo")?;
     cx.export_class::<JsFile>("File")?;
     cx.export_class::<JsVersionReader>("VersionReader")?;
+    cx.export_class::<JsWalker>("Walker")?;
+    cx.export_class::<JsTarReader>("TarReader")?;
+    cx.export_class::<JsTarWriter>("TarWriter")?;
     Ok(())
 });
@@ -0,0 +1,245 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+
+use zbox::{Error, OpenOptions, Repo};
+
+use stage;
+
+/// Name of the hidden directory holding binding-managed metadata.
+///
+/// The directory lives under root and is excluded from directory listing
+/// and tree walking. Like everything else in the repo, its content is
+/// encrypted.
+pub const META_DIR_NAME: &str = ".zbox-nodejs";
+
+/// Returns absolute path of a metadata entry.
+pub fn meta_path(name: &str) -> PathBuf {
+    Path::new("/").join(META_DIR_NAME).join(name)
+}
+
+// metadata directory mirroring the repo tree, holding entries of each file
+const FILES_META: &str = "files";
+
+/// Options a file is created with.
+///
+/// ZboxFS doesn't expose options of an existing file, so options set
+/// explicitly on creation are recorded as a per-file metadata entry.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct FileOptions {
+    pub version_limit: Option<u8>,
+    pub dedup_chunk: Option<bool>,
+}
+
+// name of the per-file metadata entry holding file options
+const OPTIONS_ENTRY: &str = ".options.json";
+
+/// Returns whether the path is the metadata directory or is inside it.
+pub fn is_meta_path(path: &Path) -> bool {
+    path.starts_with(Path::new("/").join(META_DIR_NAME))
+}
+
+/// Load a metadata entry, returns `None` if it doesn't exist.
+pub fn load<T: DeserializeOwned>(
+    repo: &mut Repo,
+    name: &str,
+) -> Result<Option<T>, Error> {
+    let path = meta_path(name);
+    if !repo.is_file(&path)? {
+        return Ok(None);
+    }
+    let mut buf = Vec::new();
+    let mut file = repo.open_file(&path)?;
+    file.read_to_end(&mut buf)?;
+    let val = serde_json::from_slice(&buf)?;
+    Ok(Some(val))
+}
+
+// replace content of a metadata entry
+//
+// Metadata has no use of history, so only one version is kept. It is
+// replaced as a whole so an interrupted write leaves the old content.
+fn write(repo: &mut Repo, name: &str, buf: &[u8]) -> Result<(), Error> {
+    let path = meta_path(name);
+    repo.create_dir_all(path.parent().unwrap())?;
+    stage::write_version(repo, &mut &buf[..], &path, 1)
+}
+
+/// Save a metadata entry, overwriting existing one.
+pub fn save<T: Serialize>(
+    repo: &mut Repo,
+    name: &str,
+    val: &T,
+) -> Result<(), Error> {
+    let buf = serde_json::to_vec(val)?;
+    write(repo, name, &buf)
+}
+
+/// Remove a metadata entry if it exists.
+pub fn remove(repo: &mut Repo, name: &str) -> Result<(), Error> {
+    let path = meta_path(name);
+    if repo.is_file(&path)? {
+        repo.remove_file(&path)?;
+    }
+    Ok(())
+}
+
+// metadata name of the directory mirroring a path
+fn file_dir_name(path: &Path) -> Result<String, Error> {
+    match path.to_str() {
+        Some(path) if path.starts_with('/') && path.len() > 1 => {
+            Ok(format!("{}{}", FILES_META, path))
+        }
+        _ => Err(Error::InvalidPath),
+    }
+}
+
+/// Returns name of a metadata entry kept for a file.
+///
+/// Entries of a file live in a directory mirroring its path, so moving or
+/// dropping them along with the file is one operation, see `file_removed`.
+pub fn file_entry_name(path: &Path, entry: &str) -> Result<String, Error> {
+    Ok(format!("{}/{}", file_dir_name(path)?, entry))
+}
+
+/// Drop or move metadata entries of a removed path, and of files under it.
+///
+/// Entries are keyed by path, so this keeps a new file at the same path from
+/// inheriting them. Entries are moved if the path is renamed, replacing
+/// those of the overwritten target. Nothing is written if there are none.
+pub fn file_removed(
+    repo: &mut Repo,
+    path: &Path,
+    renamed_to: Option<&Path>,
+) -> Result<(), Error> {
+    let from = meta_path(&file_dir_name(path)?);
+    match renamed_to {
+        Some(to) => {
+            let to = meta_path(&file_dir_name(to)?);
+            if repo.is_dir(&to)? {
+                repo.remove_dir_all(&to)?;
+            }
+            if repo.is_dir(&from)? {
+                repo.create_dir_all(to.parent().unwrap())?;
+                repo.rename(&from, &to)?;
+            }
+        }
+        None => {
+            if repo.is_dir(&from)? {
+                repo.remove_dir_all(&from)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Load recorded options of a file, empty if none were recorded.
+pub fn file_options(
+    repo: &mut Repo,
+    path: &Path,
+) -> Result<FileOptions, Error> {
+    let name = file_entry_name(path, OPTIONS_ENTRY)?;
+    Ok(load(repo, &name)?.unwrap_or_default())
+}
+
+/// Record options a file is created with, replacing recorded ones.
+pub fn save_file_options(
+    repo: &mut Repo,
+    path: &Path,
+    opts: FileOptions,
+) -> Result<(), Error> {
+    let name = file_entry_name(path, OPTIONS_ENTRY)?;
+    if opts.version_limit.is_none() && opts.dedup_chunk.is_none() {
+        return remove(repo, &name);
+    }
+    save(repo, &name, &opts)
+}
+
+/// Record options of a file for its copy, which ZboxFS creates with the
+/// same options.
+pub fn copy_file_options(
+    repo: &mut Repo,
+    from: &Path,
+    to: &Path,
+) -> Result<(), Error> {
+    let opts = file_options(repo, from)?;
+    save_file_options(repo, to, opts)
+}
+
+/// Append a record to a metadata log, which is one JSON value per line.
+///
+/// Only the new record is written, returns length of the log after it.
+pub fn append<T: Serialize>(
+    repo: &mut Repo,
+    name: &str,
+    val: &T,
+) -> Result<usize, Error> {
+    let path = meta_path(name);
+    let mut buf = serde_json::to_vec(val)?;
+    buf.push(b'\n');
+    repo.create_dir_all(path.parent().unwrap())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .version_limit(1)
+        .open(repo, &path)?;
+    file.write_once(&buf)?;
+    Ok(file.metadata()?.content_len())
+}
+
+// read lines of a metadata log, empty if it doesn't exist
+fn read_lines(repo: &mut Repo, name: &str) -> Result<Vec<Vec<u8>>, Error> {
+    let path = meta_path(name);
+    if !repo.is_file(&path)? {
+        return Ok(Vec::new());
+    }
+    let mut buf = Vec::new();
+    let mut file = repo.open_file(&path)?;
+    file.read_to_end(&mut buf)?;
+    Ok(buf
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_vec())
+        .collect())
+}
+
+/// Load all records of a metadata log, oldest first.
+///
+/// Lines which cannot be parsed are skipped.
+pub fn load_log<T: DeserializeOwned>(
+    repo: &mut Repo,
+    name: &str,
+) -> Result<Vec<T>, Error> {
+    Ok(read_lines(repo, name)?
+        .iter()
+        .filter_map(|line| serde_json::from_slice(line).ok())
+        .collect())
+}
+
+/// Drop oldest records of a metadata log, keeping at most `max_records`
+/// records in at most `max_len` bytes.
+pub fn compact_log(
+    repo: &mut Repo,
+    name: &str,
+    max_records: usize,
+    max_len: usize,
+) -> Result<(), Error> {
+    let mut kept = Vec::new();
+    let mut len = 0;
+    for line in read_lines(repo, name)?.into_iter().rev() {
+        if kept.len() >= max_records || len + line.len() + 1 > max_len {
+            break;
+        }
+        len += line.len() + 1;
+        kept.push(line);
+    }
+    let mut buf = Vec::with_capacity(len);
+    for line in kept.iter().rev() {
+        buf.extend_from_slice(line);
+        buf.push(b'\n');
+    }
+    write(repo, name, &buf)
+}
@@ -0,0 +1,108 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use zbox::{Error, OpenOptions, Repo};
+
+use stage;
+use transfer::{Progress, TransferError};
+use walk;
+
+/// Summary of a repo migration.
+#[derive(Debug, Default)]
+pub struct MigrateReport {
+    pub files: usize,
+    pub dirs: usize,
+    pub versions: usize,
+    pub bytes: usize,
+}
+
+// copy current content of a file to the other repo
+fn copy_current(
+    src: &mut Repo,
+    dst: &mut Repo,
+    path: &Path,
+) -> Result<(usize, usize), Error> {
+    let mut from = src.open_file(path)?;
+    let mut to = OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(dst, path)?;
+    let written = io::copy(&mut from, &mut to)? as usize;
+    if written > 0 {
+        to.finish()?;
+    }
+    Ok((1, written))
+}
+
+// replay every retained version of a file, oldest first, in the other repo
+fn copy_history(
+    src: &mut Repo,
+    dst: &mut Repo,
+    path: &Path,
+) -> Result<(usize, usize), Error> {
+    let hist = src.history(path)?;
+    let version_limit = dst.info()?.version_limit().max(hist.len() as u8);
+    let from = src.open_file(path)?;
+    let mut bytes = 0;
+    for ver in hist.iter() {
+        let mut rdr = from.version_reader(ver.num())?;
+        stage::write_version(dst, &mut rdr, path, version_limit)?;
+        bytes += ver.content_len();
+    }
+    Ok((hist.len(), bytes))
+}
+
+/// Copy the whole tree of a repo into another repo.
+///
+/// Directories are created and files copied in pre-order under the same
+/// paths. With `all_versions`, every retained version of a file is copied in
+/// order, otherwise only the current content. Version numbers are not kept,
+/// so binding-managed metadata which refers to them, such as snapshots,
+/// notes and labels, is not migrated.
+pub fn migrate<F>(
+    src: &mut Repo,
+    dst: &mut Repo,
+    all_versions: bool,
+    progress: &mut F,
+) -> Result<MigrateReport, TransferError>
+where
+    F: FnMut(&Progress) -> Result<(), Error>,
+{
+    let root = Path::new("/");
+    let mut ents: Vec<(PathBuf, bool)> = Vec::new();
+    walk::walk(src, root, &mut |ent| {
+        ents.push((ent.path().to_path_buf(), ent.metadata().is_dir()));
+        Ok(())
+    })
+    .map_err(|err| TransferError::new(err, root, 0))?;
+
+    let mut report = MigrateReport::default();
+    let total = ents.len();
+    for (done, (path, is_dir)) in ents.iter().enumerate() {
+        let result = if *is_dir {
+            dst.create_dir_all(path).map(|_| report.dirs += 1)
+        } else {
+            let copied = if all_versions {
+                copy_history(src, dst, path)
+            } else {
+                copy_current(src, dst, path)
+            };
+            copied.map(|(versions, bytes)| {
+                report.files += 1;
+                report.versions += versions;
+                report.bytes += bytes;
+            })
+        };
+        result
+            .and_then(|_| {
+                progress(&Progress {
+                    done: done + 1,
+                    total,
+                    path: path.to_str().unwrap(),
+                })
+            })
+            .map_err(|err| TransferError::new(err, path, done))?;
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use zbox::{Error, Metadata, Repo};
+
+use changes;
+use meta;
+use stage;
+use time_to_f64;
+use walk;
+
+// metadata entry name of mirror state, kept in the target repo
+const MIRROR_META: &str = "mirror.json";
+
+/// Options of mirroring a directory tree.
+#[derive(Debug)]
+pub struct MirrorOptions {
+    /// Directory to mirror, same path is used in both repos
+    pub path: PathBuf,
+
+    /// Remove entries in target which don't exist in source
+    pub delete_extraneous: bool,
+
+    /// Only report what would change
+    pub dry_run: bool,
+}
+
+impl Default for MirrorOptions {
+    fn default() -> Self {
+        MirrorOptions {
+            path: PathBuf::from("/"),
+            delete_extraneous: false,
+            dry_run: false,
+        }
+    }
+}
+
+/// Changes done, or to be done in a dry run, to the target repo.
+#[derive(Debug, Default)]
+pub struct MirrorReport {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub deleted: Vec<String>,
+    pub bytes: usize,
+}
+
+// source file metadata recorded when it was last copied to target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct FileState {
+    content_len: usize,
+    curr_version: usize,
+    modified_at: f64,
+}
+
+impl FileState {
+    fn new(md: &Metadata) -> Self {
+        FileState {
+            content_len: md.content_len(),
+            curr_version: md.curr_version(),
+            modified_at: time_to_f64(md.modified_at()),
+        }
+    }
+}
+
+// copy current content of a source file as a new version of target file
+fn copy_file(src: &mut Repo, dst: &mut Repo, path: &Path) -> Result<(), Error> {
+    let version_limit = dst.info()?.version_limit();
+    let file = src.open_file(path)?;
+    let curr_version = file.curr_version()?;
+    let mut rdr = file.version_reader(curr_version)?;
+    stage::write_version(dst, &mut rdr, path, version_limit)
+}
+
+/// Make a directory tree in target repo the same as in source repo.
+///
+/// A file is copied if it is missing in target, or its `contentLen`,
+/// `currVersion` or `modifiedAt` in source differs from when it was last
+/// copied. As version numbers and times are not kept across repos, the
+/// source metadata of copied files is recorded in target's binding-managed
+/// metadata.
+pub fn mirror(
+    src: &mut Repo,
+    dst: &mut Repo,
+    opts: &MirrorOptions,
+) -> Result<MirrorReport, Error> {
+    let root = opts.path.as_path();
+    if meta::is_meta_path(root) {
+        return Err(Error::InvalidPath);
+    }
+    if !src.is_dir(root)? {
+        return Err(Error::NotDir);
+    }
+
+    let mut src_ents: Vec<(PathBuf, Metadata)> = Vec::new();
+    walk::walk(src, root, &mut |ent| {
+        src_ents.push((ent.path().to_path_buf(), ent.metadata()));
+        Ok(())
+    })?;
+
+    let mut states: BTreeMap<String, FileState> =
+        meta::load(dst, MIRROR_META)?.unwrap_or_default();
+    let mut report = MirrorReport::default();
+
+    if dst.is_file(root)? {
+        return Err(Error::NotDir);
+    }
+    if !opts.dry_run {
+        dst.create_dir_all(root)?;
+    }
+
+    for (path, md) in src_ents.iter() {
+        let path_str = path.to_str().unwrap().to_owned();
+        let exists = dst.path_exists(path)?;
+
+        if md.is_dir() {
+            if exists && dst.is_dir(path)? {
+                continue;
+            }
+            if !opts.dry_run {
+                if exists {
+                    dst.remove_file(path)?;
+                    changes::record_removal(dst, &path_str, false, None);
+                    states.remove(&path_str);
+                }
+                dst.create_dir(path)?;
+            }
+            if exists {
+                report.modified.push(path_str);
+            } else {
+                report.added.push(path_str);
+            }
+            continue;
+        }
+
+        let state = FileState::new(md);
+        if exists {
+            let unchanged = dst.is_file(path)?
+                && dst.metadata(path)?.content_len() == state.content_len
+                && states.get(&path_str) == Some(&state);
+            if unchanged {
+                continue;
+            }
+        }
+        if !opts.dry_run {
+            if exists && dst.is_dir(path)? {
+                dst.remove_dir_all(path)?;
+                changes::record_removal(dst, &path_str, true, None);
+                states.retain(|key, _| !Path::new(key).starts_with(path));
+            }
+            copy_file(src, dst, path)?;
+        }
+        report.bytes += state.content_len;
+        states.insert(path_str.clone(), state);
+        if exists {
+            report.modified.push(path_str);
+        } else {
+            report.added.push(path_str);
+        }
+    }
+
+    if opts.delete_extraneous && dst.is_dir(root)? {
+        let keep: HashSet<&Path> =
+            src_ents.iter().map(|(path, _)| path.as_path()).collect();
+        let mut extra: Vec<(PathBuf, bool)> = Vec::new();
+        walk::walk(dst, root, &mut |ent| {
+            let path = ent.path();
+            let covered =
+                extra.last().map_or(false, |(dir, _)| path.starts_with(dir));
+            if !covered && !keep.contains(path) {
+                extra.push((path.to_path_buf(), ent.metadata().is_dir()));
+            }
+            Ok(())
+        })?;
+
+        for (path, is_dir) in extra {
+            let path_str = path.to_str().unwrap().to_owned();
+            if !opts.dry_run {
+                if is_dir {
+                    dst.remove_dir_all(&path)?;
+                } else {
+                    dst.remove_file(&path)?;
+                }
+                changes::record_removal(dst, &path_str, is_dir, None);
+            }
+            states.retain(|key, _| !Path::new(key).starts_with(&path));
+            report.deleted.push(path_str);
+        }
+    }
+
+    if !opts.dry_run {
+        meta::save(dst, MIRROR_META, &states)?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use zbox::{Error, Repo};
+
+use meta;
+
+// name of the per-file metadata entry holding notes of a file
+const NOTES_ENTRY: &str = ".notes.json";
+
+/// Annotation attached to a file version.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct VersionNote {
+    pub message: Option<String>,
+    pub author: Option<String>,
+    pub labels: Vec<String>,
+}
+
+/// Notes of all versions of a file, keyed by version number.
+pub type FileNotes = BTreeMap<usize, VersionNote>;
+
+// metadata name of the notes entry of a file
+fn entry_name(path: &Path) -> Result<String, Error> {
+    meta::file_entry_name(path, NOTES_ENTRY)
+}
+
+// load notes of a file, modify them and save them back
+fn update<F>(repo: &mut Repo, path: &Path, f: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut FileNotes),
+{
+    let name = entry_name(path)?;
+    let mut file_notes: FileNotes =
+        meta::load(repo, &name)?.unwrap_or_default();
+    f(&mut file_notes);
+    if file_notes.is_empty() {
+        meta::remove(repo, &name)
+    } else {
+        meta::save(repo, &name, &file_notes)
+    }
+}
+
+/// Get notes of all versions of a file.
+pub fn file_notes(repo: &mut Repo, path: &Path) -> Result<FileNotes, Error> {
+    Ok(meta::load(repo, &entry_name(path)?)?.unwrap_or_default())
+}
+
+/// Set commit message and author of a file version.
+pub fn annotate(
+    repo: &mut Repo,
+    path: &Path,
+    ver_num: usize,
+    message: Option<String>,
+    author: Option<String>,
+) -> Result<(), Error> {
+    repo.history(path)?
+        .iter()
+        .find(|ver| ver.num() == ver_num)
+        .ok_or(Error::NoVersion)?;
+    update(repo, path, |file_notes| {
+        let note = file_notes.entry(ver_num).or_insert_with(Default::default);
+        note.message = message;
+        note.author = author;
+    })
+}
+
+/// Attach a label to a file version.
+pub fn label(
+    repo: &mut Repo,
+    path: &Path,
+    ver_num: usize,
+    label: String,
+) -> Result<(), Error> {
+    repo.history(path)?
+        .iter()
+        .find(|ver| ver.num() == ver_num)
+        .ok_or(Error::NoVersion)?;
+    update(repo, path, |file_notes| {
+        let note = file_notes.entry(ver_num).or_insert_with(Default::default);
+        if !note.labels.contains(&label) {
+            note.labels.push(label);
+        }
+    })
+}
+
+/// Re-key notes of a file after its versions are renumbered.
+///
+/// `renumber` maps old version number to the new one, notes of versions
+/// not in the map are dropped.
+pub fn renumber(
+    repo: &mut Repo,
+    path: &Path,
+    renumber: &BTreeMap<usize, usize>,
+) -> Result<(), Error> {
+    if file_notes(repo, path)?.is_empty() {
+        return Ok(());
+    }
+    update(repo, path, |file_notes| {
+        let old = ::std::mem::replace(file_notes, FileNotes::new());
+        for (ver_num, note) in old {
+            if let Some(&new_num) = renumber.get(&ver_num) {
+                file_notes.insert(new_num, note);
+            }
+        }
+    })
+}
@@ -0,0 +1,189 @@
+use std::cmp::Ordering;
+use std::path::Path;
+use std::str::FromStr;
+
+use zbox::{DirEntry, Error, Repo};
+
+use meta;
+use time_to_f64;
+
+/// Sort key of directory entries.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SortBy {
+    Name,
+    ModifiedAt,
+    Size,
+}
+
+impl FromStr for SortBy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "name" => Ok(SortBy::Name),
+            "modifiedAt" => Ok(SortBy::ModifiedAt),
+            "size" => Ok(SortBy::Size),
+            _ => Err(Error::InvalidArgument),
+        }
+    }
+}
+
+impl SortBy {
+    fn key(self, ent: &DirEntry) -> f64 {
+        let md = ent.metadata();
+        match self {
+            SortBy::Name => 0.0,
+            SortBy::ModifiedAt => time_to_f64(md.modified_at()),
+            SortBy::Size => md.content_len() as f64,
+        }
+    }
+}
+
+/// Position after the last entry of a page, with the sorting it was made in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Cursor {
+    sort_by: SortBy,
+    desc: bool,
+    key: f64,
+    name: String,
+}
+
+impl Cursor {
+    // encode as an opaque hex string
+    fn encode(&self) -> String {
+        serde_json::to_vec(self)
+            .unwrap()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn decode(s: &str) -> Result<Self, Error> {
+        if s.len() % 2 != 0 || !s.is_ascii() {
+            return Err(Error::InvalidArgument);
+        }
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| Error::InvalidArgument)?;
+        serde_json::from_slice(&bytes).map_err(|_| Error::InvalidArgument)
+    }
+}
+
+/// Options of reading one page of a directory.
+#[derive(Debug)]
+pub struct PageOptions {
+    /// Number of entries to skip, cannot be combined with a cursor
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+    pub sort_by: SortBy,
+    pub desc: bool,
+
+    /// Cursor returned with the previous page, its sorting takes precedence
+    pub cursor: Option<String>,
+}
+
+impl Default for PageOptions {
+    fn default() -> Self {
+        PageOptions {
+            offset: None,
+            limit: None,
+            sort_by: SortBy::Name,
+            desc: false,
+            cursor: None,
+        }
+    }
+}
+
+/// One page of directory entries.
+pub struct Page {
+    pub entries: Vec<DirEntry>,
+
+    /// Cursor to continue from, none if this is the last page
+    pub cursor: Option<String>,
+}
+
+/// Read one page of a directory in the requested order.
+///
+/// Continuing from a cursor resumes after the last entry of the previous
+/// page, so entries added or removed meanwhile do not shift the pages. An
+/// offset is relative to the start of the directory, so it is rejected
+/// together with a cursor.
+///
+/// ZboxFS can only list a whole directory, so every page lists and sorts all
+/// entries, which costs O(n log n) for a directory of n entries per page.
+pub fn read_dir_page(
+    repo: &Repo,
+    path: &Path,
+    opts: &PageOptions,
+) -> Result<Page, Error> {
+    if opts.cursor.is_some() && opts.offset.is_some() {
+        return Err(Error::InvalidArgument);
+    }
+    let after = match opts.cursor {
+        Some(ref s) => Some(Cursor::decode(s)?),
+        None => None,
+    };
+    let (sort_by, desc) = match after {
+        Some(ref cursor) => (cursor.sort_by, cursor.desc),
+        None => (opts.sort_by, opts.desc),
+    };
+
+    let compare = |key_a: f64, name_a: &str, key_b: f64, name_b: &str| {
+        let ord = key_a
+            .partial_cmp(&key_b)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| name_a.cmp(name_b));
+        if desc {
+            ord.reverse()
+        } else {
+            ord
+        }
+    };
+
+    let mut ents: Vec<(f64, DirEntry)> = repo
+        .read_dir(path)?
+        .into_iter()
+        .filter(|ent| !meta::is_meta_path(ent.path()))
+        .map(|ent| (sort_by.key(&ent), ent))
+        .filter(|(key, ent)| match after {
+            Some(ref cursor) => {
+                compare(*key, ent.file_name(), cursor.key, &cursor.name)
+                    == Ordering::Greater
+            }
+            None => true,
+        })
+        .collect();
+    ents.sort_by(|(key_a, a), (key_b, b)| {
+        compare(*key_a, a.file_name(), *key_b, b.file_name())
+    });
+
+    let total = ents.len();
+    let offset = opts.offset.unwrap_or(0);
+    let end = match opts.limit {
+        Some(limit) => total.min(offset.saturating_add(limit)),
+        None => total,
+    };
+    let entries: Vec<(f64, DirEntry)> =
+        ents.into_iter().take(end).skip(offset).collect();
+
+    let cursor = if end < total {
+        entries.last().map(|(key, ent)| {
+            Cursor {
+                sort_by,
+                desc,
+                key: *key,
+                name: ent.file_name().to_owned(),
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    Ok(Page {
+        entries: entries.into_iter().map(|(_, ent)| ent).collect(),
+        cursor,
+    })
+}
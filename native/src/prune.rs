@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use zbox::{Error, Repo, Version};
+
+use meta;
+use notes;
+use snapshot::{self, Snapshot};
+use stage;
+use time_to_f64;
+use walk;
+
+/// Rules deciding which history versions of a file are dropped.
+///
+/// The current version of a file, and versions recorded in snapshots, are
+/// always kept.
+#[derive(Default)]
+pub struct PrunePolicy {
+    /// Keep at most this many latest versions
+    pub keep: Option<usize>,
+
+    /// Drop versions created before this time, in seconds since epoch
+    pub older_than: Option<f64>,
+}
+
+impl PrunePolicy {
+    fn is_retired(&self, idx: usize, ver: &Version, total: usize) -> bool {
+        if idx + 1 == total {
+            return false;
+        }
+        let over_keep =
+            self.keep.map_or(false, |keep| idx + keep.max(1) < total);
+        let too_old = self
+            .older_than
+            .map_or(false, |time| time_to_f64(ver.created_at()) < time);
+        over_keep || too_old
+    }
+}
+
+/// Outcome of pruning.
+#[derive(Default)]
+pub struct PruneReport {
+    pub files: usize,
+    pub versions: usize,
+
+    /// Logical content length of the dropped versions, storage space
+    /// actually reclaimed can be less as content is deduplicated
+    pub bytes: usize,
+
+    /// Old to new version numbers of kept versions, for each pruned file
+    pub renumbered: BTreeMap<String, BTreeMap<usize, usize>>,
+}
+
+/// Drop history versions of a file according to policy.
+///
+/// ZboxFS cannot remove a single version in place, so the kept versions are
+/// replayed in order into a new file which then replaces the original. As a
+/// result, the kept versions are renumbered from 1 and get new creation time,
+/// version notes and versions recorded in snapshots are renumbered
+/// accordingly and the renumbering is returned in the report. The new file
+/// gets the options the original was created with, if they were recorded.
+/// Handles already open on the file keep referring to the replaced original
+/// and don't see the pruned file.
+pub fn prune_history(
+    repo: &mut Repo,
+    path: &Path,
+    policy: &PrunePolicy,
+) -> Result<PruneReport, Error> {
+    let mut snapshots = snapshot::list(repo)?;
+    prune_file(repo, path, policy, &mut snapshots)
+}
+
+fn prune_file(
+    repo: &mut Repo,
+    path: &Path,
+    policy: &PrunePolicy,
+    snapshots: &mut [Snapshot],
+) -> Result<PruneReport, Error> {
+    let path_str = path.to_str().ok_or(Error::InvalidPath)?;
+    let in_snapshot: BTreeSet<usize> = snapshots
+        .iter()
+        .filter_map(|snapshot| snapshot.files.get(path_str).cloned())
+        .collect();
+    let hist = repo.history(path)?;
+    let total = hist.len();
+    let (retired, kept): (Vec<_>, Vec<_>) =
+        hist.into_iter().enumerate().partition(|&(idx, ref ver)| {
+            policy.is_retired(idx, ver, total)
+                && !in_snapshot.contains(&ver.num())
+        });
+
+    let mut report = PruneReport::default();
+    if retired.is_empty() {
+        return Ok(report);
+    }
+
+    // clean up leftover from a previously interrupted pruning
+    let tmp = stage::sibling(path, "prune")?;
+    stage::remove_leftover(repo, &tmp)?;
+
+    // history length is bounded by version limit, but clamp to be safe
+    let kept_len = kept.len().min(u8::MAX as usize) as u8;
+    let mut opts = meta::file_options(repo, path)?;
+    let version_limit = match opts.version_limit {
+        Some(version_limit) => version_limit,
+        None => repo.info()?.version_limit(),
+    };
+    opts.version_limit = Some(version_limit.max(kept_len));
+    let src = repo.open_file(path)?;
+    for (_, ver) in kept.iter() {
+        let mut rdr = src.version_reader(ver.num())?;
+        stage::write_version_with(repo, &mut rdr, &tmp, opts)?;
+    }
+    drop(src);
+
+    repo.rename(&tmp, path)?;
+
+    let renumber: BTreeMap<usize, usize> = kept
+        .iter()
+        .enumerate()
+        .map(|(idx, &(_, ref ver))| (ver.num(), idx + 1))
+        .collect();
+    notes::renumber(repo, path, &renumber)?;
+    snapshot::renumber(repo, snapshots, path, &renumber)?;
+
+    report.files = 1;
+    report.versions = retired.len();
+    report.bytes = retired.iter().map(|(_, ver)| ver.content_len()).sum();
+    report.renumbered.insert(path_str.to_owned(), renumber);
+    Ok(report)
+}
+
+/// Drop history versions of every file in the repo according to policy.
+pub fn prune_all_history(
+    repo: &mut Repo,
+    policy: &PrunePolicy,
+) -> Result<PruneReport, Error> {
+    let mut snapshots = snapshot::list(repo)?;
+    let mut report = PruneReport::default();
+    for path in walk::file_paths(repo, Path::new("/"))? {
+        let pruned =
+            prune_file(repo, Path::new(&path), policy, &mut snapshots)?;
+        report.files += pruned.files;
+        report.versions += pruned.versions;
+        report.bytes += pruned.bytes;
+        report.renumbered.extend(pruned.renumbered);
+    }
+    Ok(report)
+}
@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::SystemTime;
+
+use zbox::{Error, Repo};
+
+use changes;
+use meta;
+use stage;
+use time_to_f64;
+use walk;
+
+// metadata directory holding snapshot manifests
+const SNAPSHOTS_META: &str = "snapshots";
+
+/// Point-in-time record of the whole repo tree.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub created_at: f64,
+    pub dirs: BTreeSet<String>,
+    pub files: BTreeMap<String, usize>,
+}
+
+/// Outcome of restoring a snapshot.
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub restored: usize,
+    pub removed: usize,
+    pub missing: Vec<String>,
+}
+
+fn manifest_name(name: &str) -> Result<String, Error> {
+    if name.is_empty() || name.starts_with('.') || name.contains('/') {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(format!("{}/{}.json", SNAPSHOTS_META, name))
+}
+
+/// Record current version of every file into a new snapshot.
+pub fn create(repo: &mut Repo, name: &str) -> Result<Snapshot, Error> {
+    let manifest = manifest_name(name)?;
+    if meta::load::<Snapshot>(repo, &manifest)?.is_some() {
+        return Err(Error::AlreadyExists);
+    }
+
+    let mut snapshot = Snapshot {
+        name: name.to_owned(),
+        created_at: time_to_f64(SystemTime::now()),
+        ..Default::default()
+    };
+    walk::walk(repo, Path::new("/"), &mut |ent| {
+        let path = ent.path().to_str().unwrap().to_owned();
+        let md = ent.metadata();
+        if md.is_dir() {
+            snapshot.dirs.insert(path);
+        } else {
+            snapshot.files.insert(path, md.curr_version());
+        }
+        Ok(())
+    })?;
+
+    meta::save(repo, &manifest, &snapshot)?;
+    Ok(snapshot)
+}
+
+/// Load a snapshot by its name.
+pub fn open(repo: &mut Repo, name: &str) -> Result<Snapshot, Error> {
+    let manifest = manifest_name(name)?;
+    meta::load(repo, &manifest)?.ok_or(Error::NotFound)
+}
+
+/// List all snapshots, ordered by creation time.
+pub fn list(repo: &mut Repo) -> Result<Vec<Snapshot>, Error> {
+    let dir = meta::meta_path(SNAPSHOTS_META);
+    if !repo.is_dir(&dir)? {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for ent in repo.read_dir(&dir)? {
+        let manifest = format!("{}/{}", SNAPSHOTS_META, ent.file_name());
+        if let Some(snapshot) = meta::load::<Snapshot>(repo, &manifest)? {
+            snapshots.push(snapshot);
+        }
+    }
+    snapshots.sort_by(|a, b| a.created_at.partial_cmp(&b.created_at).unwrap());
+    Ok(snapshots)
+}
+
+/// Re-key versions of a file recorded in snapshots after its versions are
+/// renumbered, changed manifests are saved.
+///
+/// `renumber` maps old version number to the new one. It must cover every
+/// version of the file recorded in the snapshots, so nothing is left
+/// pointing at a dropped version.
+pub fn renumber(
+    repo: &mut Repo,
+    snapshots: &mut [Snapshot],
+    path: &Path,
+    renumber: &BTreeMap<usize, usize>,
+) -> Result<(), Error> {
+    let path = path.to_str().ok_or(Error::InvalidPath)?;
+    for snapshot in snapshots.iter_mut() {
+        let new_num = match snapshot.files.get(path) {
+            Some(ver_num) => {
+                *renumber.get(ver_num).ok_or(Error::InvalidArgument)?
+            }
+            None => continue,
+        };
+        if snapshot.files[path] == new_num {
+            continue;
+        }
+        snapshot.files.insert(path.to_owned(), new_num);
+        meta::save(repo, &manifest_name(&snapshot.name)?, &snapshot)?;
+    }
+    Ok(())
+}
+
+/// Bring the whole repo tree back to the state recorded in a snapshot.
+///
+/// Entries created after the snapshot are removed, and files whose current
+/// version differs get the recorded version content as a new version.
+/// Files removed since the snapshot, or whose recorded version has been
+/// retired, cannot be recovered and are reported as missing.
+pub fn restore(repo: &mut Repo, name: &str) -> Result<RestoreReport, Error> {
+    let snapshot = open(repo, name)?;
+    let mut report = RestoreReport::default();
+
+    // find entries not in snapshot, in pre-order so parents come first
+    let mut extra_dirs = Vec::new();
+    let mut extra_files = Vec::new();
+    walk::walk(repo, Path::new("/"), &mut |ent| {
+        let path = ent.path().to_str().unwrap().to_owned();
+        if ent.metadata().is_dir() {
+            if !snapshot.dirs.contains(&path) {
+                extra_dirs.push(path);
+            }
+        } else if !snapshot.files.contains_key(&path) {
+            extra_files.push(path);
+        }
+        Ok(())
+    })?;
+
+    // remove extraneous dirs, skipping those already removed with parent
+    let mut removed_dirs: Vec<String> = Vec::new();
+    for dir in extra_dirs {
+        if removed_dirs
+            .iter()
+            .any(|rm| Path::new(&dir).starts_with(rm))
+        {
+            continue;
+        }
+        repo.remove_dir_all(&dir)?;
+        changes::record_removal(repo, &dir, true, None);
+        removed_dirs.push(dir);
+        report.removed += 1;
+    }
+    for file in extra_files {
+        if removed_dirs
+            .iter()
+            .any(|rm| Path::new(&file).starts_with(rm))
+        {
+            continue;
+        }
+        repo.remove_file(&file)?;
+        changes::record_removal(repo, &file, false, None);
+        report.removed += 1;
+    }
+
+    for dir in snapshot.dirs.iter() {
+        repo.create_dir_all(dir)?;
+    }
+
+    let version_limit = repo.info()?.version_limit();
+    for (path, &ver_num) in snapshot.files.iter() {
+        let path = Path::new(path);
+        if !repo.is_file(path)? {
+            report.missing.push(path.to_str().unwrap().to_owned());
+            continue;
+        }
+        if repo.metadata(path)?.curr_version() == ver_num {
+            continue;
+        }
+        let rdr = repo.open_file(path)?.version_reader(ver_num);
+        match rdr {
+            Ok(mut rdr) => {
+                stage::write_version(repo, &mut rdr, path, version_limit)?;
+                report.restored += 1;
+            }
+            Err(Error::NoVersion) => {
+                report.missing.push(path.to_str().unwrap().to_owned());
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,70 @@
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use zbox::{Error, OpenOptions, Repo};
+
+use meta::FileOptions;
+
+/// Make a hidden sibling path of a file, used for temporary files.
+pub fn sibling(path: &Path, suffix: &str) -> Result<PathBuf, Error> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or(Error::InvalidPath)?;
+    Ok(path.with_file_name(format!(".~{}.{}", name, suffix)))
+}
+
+/// Remove a temporary file left over by an interrupted operation.
+pub fn remove_leftover(repo: &mut Repo, path: &Path) -> Result<(), Error> {
+    if repo.is_file(path)? {
+        repo.remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Add content of a reader to a file as exactly one new version.
+///
+/// Writing to an existing file cannot shrink its content without an extra
+/// truncating version, so the content is written to a fresh scratch file
+/// first and then copied over. If target file doesn't exist, it is created
+/// with the specified version limit.
+pub fn write_version<R: Read>(
+    repo: &mut Repo,
+    rdr: &mut R,
+    to: &Path,
+    version_limit: u8,
+) -> Result<(), Error> {
+    let opts = FileOptions {
+        version_limit: Some(version_limit),
+        dedup_chunk: None,
+    };
+    write_version_with(repo, rdr, to, opts)
+}
+
+/// Same as `write_version`, but a new target file is created with the
+/// specified options, unset ones fall back to repo defaults.
+pub fn write_version_with<R: Read>(
+    repo: &mut Repo,
+    rdr: &mut R,
+    to: &Path,
+    opts: FileOptions,
+) -> Result<(), Error> {
+    let stage = sibling(to, "stage")?;
+    remove_leftover(repo, &stage)?;
+    {
+        let mut options = OpenOptions::new();
+        options.create_new(true).write(true);
+        if let Some(version_limit) = opts.version_limit {
+            options.version_limit(version_limit);
+        }
+        if let Some(dedup_chunk) = opts.dedup_chunk {
+            options.dedup_chunk(dedup_chunk);
+        }
+        let mut stage_file = options.open(repo, &stage)?;
+        if io::copy(rdr, &mut stage_file)? > 0 {
+            stage_file.finish()?;
+        }
+    }
+    repo.copy(&stage, to)?;
+    repo.remove_file(&stage)
+}
@@ -0,0 +1,392 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use zbox::Error;
+use zeroize::Zeroizing;
+
+// The super block is two identical copies, or arms, of the same block. Its
+// format is private to zbox, so reading it here without opening the repo
+// mirrors zbox 0.8:
+//
+//   head: salt (16 bytes) + cost (1 byte) + cipher (1 byte), not encrypted
+//   body: AEAD encrypted with the password hash as key and `MAGIC` as
+//         additional data, the nonce is prepended to the cipher text.
+//         Decrypted, it is body length (8 bytes LE) + msgpack body, whose
+//         first field is the arm sequence number.
+
+// super block file name stem, suffix is the arm number
+const SUPER_BLK_STEM: &str = "super_blk";
+const ARMS: [u64; 2] = [0, 1];
+
+const SALT_SIZE: usize = 16;
+const HEAD_LEN: usize = SALT_SIZE + 2;
+const KEY_SIZE: usize = 32;
+const ATAG_SIZE: usize = 16;
+const XCHACHA_NONCE_SIZE: usize = 24;
+const AES_NONCE_SIZE: usize = 28;
+
+// additional data of body encryption
+const MAGIC: [u8; 4] = [233, 239, 241, 251];
+
+// AES nonce extension const
+const NONCE_EXT_CONST: [u8; 16] = [
+    0x32, 0xb9, 0xa5, 0xb8, 0xb1, 0x96, 0x83, 0x85, 0xa3, 0x4e, 0x47, 0x97,
+    0x0d, 0x82, 0xc1, 0x6d,
+];
+
+// Argon2id v1.3, as used by zbox
+const PWHASH_ALG: i32 = 2;
+
+// libsodium is linked in by zbox
+extern "C" {
+    fn sodium_init() -> i32;
+    fn crypto_pwhash(
+        out: *mut u8,
+        outlen: u64,
+        passwd: *const u8,
+        passwdlen: u64,
+        salt: *const u8,
+        opslimit: u64,
+        memlimit: usize,
+        alg: i32,
+    ) -> i32;
+    fn crypto_core_hchacha20(
+        out: *mut u8,
+        inbuf: *const u8,
+        k: *const u8,
+        c: *const u8,
+    ) -> i32;
+    fn crypto_aead_xchacha20poly1305_ietf_decrypt(
+        m: *mut u8,
+        mlen_p: *mut u64,
+        nsec: *mut u8,
+        c: *const u8,
+        clen: u64,
+        ad: *const u8,
+        adlen: u64,
+        npub: *const u8,
+        k: *const u8,
+    ) -> i32;
+    fn crypto_aead_aes256gcm_is_available() -> i32;
+    fn crypto_aead_aes256gcm_decrypt(
+        m: *mut u8,
+        mlen_p: *mut u64,
+        nsec: *mut u8,
+        c: *const u8,
+        clen: u64,
+        ad: *const u8,
+        adlen: u64,
+        npub: *const u8,
+        k: *const u8,
+    ) -> i32;
+}
+
+/// Status of a super block arm.
+#[derive(Debug)]
+pub struct ArmStatus {
+    /// The arm exists in storage
+    pub present: bool,
+
+    /// Sequence number, set if the arm can be decrypted and parsed
+    pub seq: Option<u64>,
+
+    /// Decrypted body, set along with the sequence number
+    pub body: Option<Zeroizing<Vec<u8>>>,
+
+    /// Why the arm is missing or not readable
+    pub err: Option<Error>,
+}
+
+/// Status of both super block arms.
+#[derive(Debug)]
+pub struct SuperBlockCheck {
+    pub arms: Vec<ArmStatus>,
+}
+
+impl SuperBlockCheck {
+    /// Both arms are readable and have identical bodies, so they are at the
+    /// same sequence as zbox requires to open the repo.
+    pub fn is_consistent(&self) -> bool {
+        match (&self.arms[0].body, &self.arms[1].body) {
+            (Some(left), Some(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+// get the local directory holding super block arms of a repo, none if the
+// storage is not locally readable
+//
+// For zbox storage this is the local file cache, which holds the copy
+// written or fetched when the repo was last opened on this machine. Without
+// the cache directory there is nothing local to read.
+fn super_block_dir(uri: &str) -> Result<Option<PathBuf>, Error> {
+    let idx = uri.find("://").ok_or(Error::InvalidUri)?;
+    let loc = &uri[idx + 3..];
+    if loc.is_empty() {
+        return Err(Error::InvalidUri);
+    }
+    match &uri[..idx] {
+        "file" => Ok(Some(PathBuf::from(loc))),
+        "zbox" => {
+            let loc = &loc[loc.find('@').ok_or(Error::InvalidUri)? + 1..];
+            let (repo_id, params) = match loc.find('?') {
+                Some(idx) => (&loc[..idx], &loc[idx + 1..]),
+                None => (loc, ""),
+            };
+            let mut is_file_cache = false;
+            let mut base = None;
+            for param in params.split('&').filter(|p| !p.is_empty()) {
+                let idx = param.find('=').ok_or(Error::InvalidUri)?;
+                match &param[..idx] {
+                    "cache_type" => is_file_cache = &param[idx + 1..] == "file",
+                    "base" => base = Some(&param[idx + 1..]),
+                    _ => {}
+                }
+            }
+            match base {
+                Some(base) if is_file_cache => {
+                    let dir = Path::new(base).join(repo_id);
+                    Ok(if dir.is_dir() { Some(dir) } else { None })
+                }
+                _ => Ok(None),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+// map the cost byte in head to libsodium ops and mem limits
+fn pwhash_limits(cost: u8) -> Result<(u64, usize), Error> {
+    let ops_limit = match cost & 0x0f {
+        0 => 2,
+        1 => 3,
+        2 => 4,
+        _ => return Err(Error::InvalidCost),
+    };
+    let mem_limit = match cost >> 4 {
+        0 => 67_108_864,
+        1 => 268_435_456,
+        2 => 1_073_741_824,
+        _ => return Err(Error::InvalidCost),
+    };
+    Ok((ops_limit, mem_limit))
+}
+
+// derive the body key from password and head
+fn derive_key(
+    pwd: &str,
+    head: &[u8],
+) -> Result<Zeroizing<[u8; KEY_SIZE]>, Error> {
+    let (ops_limit, mem_limit) = pwhash_limits(head[SALT_SIZE])?;
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    let result = unsafe {
+        crypto_pwhash(
+            key.as_mut_ptr(),
+            KEY_SIZE as u64,
+            pwd.as_ptr(),
+            pwd.len() as u64,
+            head.as_ptr(),
+            ops_limit,
+            mem_limit,
+            PWHASH_ALG,
+        )
+    };
+    match result {
+        0 => Ok(key),
+        _ => Err(Error::Hashing),
+    }
+}
+
+// decrypt arm body with the derived key
+fn decrypt(
+    cipher: u8,
+    ctxt: &[u8],
+    key: &[u8; KEY_SIZE],
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let nonce_size = match cipher {
+        0 => XCHACHA_NONCE_SIZE,
+        1 if unsafe { crypto_aead_aes256gcm_is_available() } == 1 => {
+            AES_NONCE_SIZE
+        }
+        1 => return Err(Error::NoAesHardware),
+        _ => return Err(Error::InvalidCipher),
+    };
+    if ctxt.len() < nonce_size + ATAG_SIZE {
+        return Err(Error::InvalidSuperBlk);
+    }
+    let (nonce, ctxt) = ctxt.split_at(nonce_size);
+    let mut msg = Zeroizing::new(vec![0u8; ctxt.len() - ATAG_SIZE]);
+    let mut msg_len = 0u64;
+    let result = unsafe {
+        if cipher == 0 {
+            crypto_aead_xchacha20poly1305_ietf_decrypt(
+                msg.as_mut_ptr(),
+                &mut msg_len,
+                ptr::null_mut(),
+                ctxt.as_ptr(),
+                ctxt.len() as u64,
+                MAGIC.as_ptr(),
+                MAGIC.len() as u64,
+                nonce.as_ptr(),
+                key.as_ptr(),
+            )
+        } else {
+            // AES nonce is extended to a sub-key and a sub-nonce
+            let mut subkey = Zeroizing::new([0u8; KEY_SIZE]);
+            crypto_core_hchacha20(
+                subkey.as_mut_ptr(),
+                nonce.as_ptr(),
+                key.as_ptr(),
+                NONCE_EXT_CONST.as_ptr(),
+            );
+            crypto_aead_aes256gcm_decrypt(
+                msg.as_mut_ptr(),
+                &mut msg_len,
+                ptr::null_mut(),
+                ctxt.as_ptr(),
+                ctxt.len() as u64,
+                MAGIC.as_ptr(),
+                MAGIC.len() as u64,
+                nonce[16..].as_ptr(),
+                subkey.as_ptr(),
+            )
+        }
+    };
+    match result {
+        0 => {
+            msg.truncate(msg_len as usize);
+            Ok(msg)
+        }
+        _ => Err(Error::Decrypt),
+    }
+}
+
+// read a big-endian unsigned integer of `len` bytes
+fn read_uint(buf: &[u8], len: usize) -> Result<u64, Error> {
+    if buf.len() < len {
+        return Err(Error::InvalidSuperBlk);
+    }
+    Ok(buf[..len].iter().fold(0, |n, b| (n << 8) | u64::from(*b)))
+}
+
+// get the msgpack body out of decrypted arm content and parse its sequence
+// number, which is the first element of the array the body struct is
+// serialized to
+fn parse_body(comp: &[u8]) -> Result<(u64, &[u8]), Error> {
+    if comp.len() < 8 {
+        return Err(Error::InvalidSuperBlk);
+    }
+    let mut len_buf = [0u8; 8];
+    len_buf.copy_from_slice(&comp[..8]);
+    let body_len = u64::from_le_bytes(len_buf) as usize;
+    if body_len > comp.len() - 8 {
+        return Err(Error::InvalidSuperBlk);
+    }
+    let body = &comp[8..8 + body_len];
+
+    let seq = match body.first() {
+        Some(0x90..=0x9f) => &body[1..],
+        Some(0xdc) if body.len() > 3 => &body[3..],
+        Some(0xdd) if body.len() > 5 => &body[5..],
+        _ => return Err(Error::InvalidSuperBlk),
+    };
+    let seq = match seq.first() {
+        Some(&n @ 0x00..=0x7f) => u64::from(n),
+        Some(0xcc) => read_uint(&seq[1..], 1)?,
+        Some(0xcd) => read_uint(&seq[1..], 2)?,
+        Some(0xce) => read_uint(&seq[1..], 4)?,
+        Some(0xcf) => read_uint(&seq[1..], 8)?,
+        _ => return Err(Error::InvalidSuperBlk),
+    };
+    Ok((seq, body))
+}
+
+// decrypt an arm and get its sequence number and body, keys derived from
+// the same head are reused as both arms are normally written with the same
+// salt
+fn read_arm(
+    buf: &[u8],
+    pwd: &str,
+    keys: &mut Vec<(Vec<u8>, Zeroizing<[u8; KEY_SIZE]>)>,
+) -> Result<(u64, Zeroizing<Vec<u8>>), Error> {
+    if buf.len() < HEAD_LEN {
+        return Err(Error::InvalidSuperBlk);
+    }
+    let head = &buf[..HEAD_LEN];
+    let pos = match keys.iter().position(|(h, _)| h.as_slice() == head) {
+        Some(pos) => pos,
+        None => {
+            keys.push((head.to_vec(), derive_key(pwd, head)?));
+            keys.len() - 1
+        }
+    };
+    let comp = decrypt(head[SALT_SIZE + 1], &buf[HEAD_LEN..], &keys[pos].1)?;
+    let (seq, body) = parse_body(&comp)?;
+    Ok((seq, Zeroizing::new(body.to_vec())))
+}
+
+/// Check both super block arms of a repo with a password.
+///
+/// The arms are read straight from local storage and decrypted, the repo is
+/// neither opened nor locked, so it can be checked while it is open
+/// elsewhere. Only file storage and zbox storage with file cache are
+/// supported, none is returned for other storages.
+///
+/// For zbox storage the local cached copy is checked, which is the super
+/// block as of the last time the repo was opened on this machine. It can be
+/// behind the remote copy if the repo was changed elsewhere since, and none
+/// is returned if the repo has no local cache.
+pub fn check(uri: &str, pwd: &str) -> Result<Option<SuperBlockCheck>, Error> {
+    let dir = match super_block_dir(uri)? {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+    unsafe {
+        if sodium_init() < 0 {
+            return Err(Error::InitCrypto);
+        }
+    }
+
+    let mut keys = Vec::new();
+    let arms = ARMS
+        .iter()
+        .map(|suffix| {
+            let path =
+                dir.join(SUPER_BLK_STEM).with_extension(suffix.to_string());
+            match fs::read(&path) {
+                Ok(buf) => match read_arm(&buf, pwd, &mut keys) {
+                    Ok((seq, body)) => ArmStatus {
+                        present: true,
+                        seq: Some(seq),
+                        body: Some(body),
+                        err: None,
+                    },
+                    Err(err) => ArmStatus {
+                        present: true,
+                        seq: None,
+                        body: None,
+                        err: Some(err),
+                    },
+                },
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                    ArmStatus {
+                        present: false,
+                        seq: None,
+                        body: None,
+                        err: Some(Error::NotFound),
+                    }
+                }
+                Err(err) => ArmStatus {
+                    present: true,
+                    seq: None,
+                    body: None,
+                    err: Some(Error::from(err)),
+                },
+            }
+        })
+        .collect();
+    Ok(Some(SuperBlockCheck { arms }))
+}
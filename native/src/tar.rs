@@ -0,0 +1,495 @@
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str;
+
+use tar_lib::{EntryType, Header};
+
+use zbox::{Error, File, Repo};
+
+use time_to_f64;
+use walk;
+
+// size of a tar block
+const BLOCK_SIZE: usize = 512;
+
+// offset and length of checksum field in a tar header
+const CKSUM_OFFSET: usize = 148;
+const CKSUM_LEN: usize = 8;
+
+// longest name fits in the name field of a header
+const MAX_NAME_LEN: usize = 100;
+
+// number of padding bytes after content to fill up a block
+fn padding(len: u64) -> u64 {
+    (BLOCK_SIZE as u64 - len % BLOCK_SIZE as u64) % BLOCK_SIZE as u64
+}
+
+// convert any error from the tar library to argument error
+fn invalid<E>(_: E) -> Error {
+    Error::InvalidArgument
+}
+
+// build header blocks of an entry, with a GNU long name entry if needed
+fn header_blocks(
+    name: &str,
+    size: u64,
+    mtime: f64,
+    is_dir: bool,
+) -> Result<Vec<u8>, Error> {
+    let mut blocks = Vec::new();
+    let mut header = Header::new_gnu();
+
+    if name.len() > MAX_NAME_LEN {
+        let mut long = Header::new_gnu();
+        long.set_path("././@LongLink").map_err(invalid)?;
+        long.set_entry_type(EntryType::GNULongName);
+        long.set_size(name.len() as u64 + 1);
+        long.set_cksum();
+        blocks.extend_from_slice(long.as_bytes());
+        blocks.extend_from_slice(name.as_bytes());
+        blocks.push(0);
+        let pad = padding(name.len() as u64 + 1) as usize;
+        blocks.extend(std::iter::repeat(0).take(pad));
+
+        // the name field keeps a truncated copy of the name
+        header.as_old_mut().name[..]
+            .copy_from_slice(&name.as_bytes()[..MAX_NAME_LEN]);
+    } else {
+        header.set_path(name).map_err(invalid)?;
+    }
+
+    if is_dir {
+        header.set_entry_type(EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+    } else {
+        header.set_entry_type(EntryType::Regular);
+        header.set_mode(0o644);
+        header.set_size(size);
+    }
+    header.set_mtime(mtime as u64);
+    header.set_cksum();
+    blocks.extend_from_slice(header.as_bytes());
+    Ok(blocks)
+}
+
+// entry to be written to a tar archive
+struct TarEntry {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+}
+
+/// Pull-based tar archive writer of a repo file or directory tree.
+///
+/// Entry names are relative to the exported path. Content is read from the
+/// repo only when the archive is pulled, one chunk at a time.
+pub struct TarExporter {
+    entries: Vec<TarEntry>,
+    next: usize,
+
+    // file whose content is being archived
+    file: Option<File>,
+    file_len: u64,
+
+    // header or padding bytes not pulled yet
+    pending: Vec<u8>,
+    pending_pos: usize,
+
+    done: bool,
+}
+
+impl TarExporter {
+    pub fn new(repo: &Repo, path: &Path) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+        if repo.is_dir(path)? {
+            walk::walk(repo, path, &mut |ent| {
+                let rel = ent.path().strip_prefix(path).unwrap();
+                let is_dir = ent.metadata().is_dir();
+                let mut name = rel.to_str().unwrap().to_owned();
+                if is_dir {
+                    name.push('/');
+                }
+                entries.push(TarEntry {
+                    path: ent.path().to_path_buf(),
+                    name,
+                    is_dir,
+                });
+                Ok(())
+            })?;
+        } else {
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or(Error::InvalidPath)?;
+            entries.push(TarEntry {
+                path: path.to_path_buf(),
+                name: name.to_owned(),
+                is_dir: false,
+            });
+        }
+
+        Ok(TarExporter {
+            entries,
+            next: 0,
+            file: None,
+            file_len: 0,
+            pending: Vec::new(),
+            pending_pos: 0,
+            done: false,
+        })
+    }
+
+    // queue header of the next entry and open its file
+    fn start_entry(&mut self, repo: &mut Repo) -> Result<(), Error> {
+        let ent = &self.entries[self.next];
+        let md = repo.metadata(&ent.path)?;
+        let mtime = time_to_f64(md.modified_at());
+        let size = md.content_len() as u64;
+        self.pending = header_blocks(&ent.name, size, mtime, ent.is_dir)?;
+        self.pending_pos = 0;
+        if !ent.is_dir {
+            self.file = Some(repo.open_file(&ent.path)?);
+            self.file_len = size;
+        }
+        self.next += 1;
+        Ok(())
+    }
+
+    /// Pull next chunk of the archive, an empty chunk means the end.
+    pub fn read_chunk(
+        &mut self,
+        repo: &mut Repo,
+        size: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::with_capacity(size);
+
+        while out.len() < size {
+            if self.pending_pos < self.pending.len() {
+                let len = (self.pending.len() - self.pending_pos)
+                    .min(size - out.len());
+                out.extend_from_slice(
+                    &self.pending[self.pending_pos..self.pending_pos + len],
+                );
+                self.pending_pos += len;
+                continue;
+            }
+
+            if let Some(ref mut file) = self.file {
+                let start = out.len();
+                out.resize(size, 0);
+                let read = file.read(&mut out[start..])?;
+                out.truncate(start + read);
+                if read > 0 {
+                    continue;
+                }
+            }
+            if self.file.take().is_some() {
+                self.pending = vec![0; padding(self.file_len) as usize];
+                self.pending_pos = 0;
+                continue;
+            }
+
+            if self.next < self.entries.len() {
+                self.start_entry(repo)?;
+                continue;
+            }
+
+            // archive ends with two zero blocks
+            if !self.done {
+                self.pending = vec![0; BLOCK_SIZE * 2];
+                self.pending_pos = 0;
+                self.done = true;
+                continue;
+            }
+            break;
+        }
+
+        Ok(out)
+    }
+}
+
+/// Outcome of unpacking an archive.
+#[derive(Debug, Default)]
+pub struct UnpackReport {
+    pub files: usize,
+    pub dirs: usize,
+    pub bytes: usize,
+
+    /// Names of entries not supported, such as links and devices
+    pub skipped: Vec<String>,
+}
+
+// what the data following a header is used for
+enum Data {
+    File { file: File, written: usize },
+    LongName(Vec<u8>),
+    Pax(Vec<u8>),
+    Skip,
+}
+
+enum State {
+    Header,
+    Data {
+        data: Data,
+        remaining: u64,
+        padding: u64,
+    },
+}
+
+// find path record in PAX extended header data
+fn pax_path(data: &[u8]) -> Result<Option<String>, Error> {
+    let mut path = None;
+    let mut rest = data;
+    while !rest.is_empty() {
+        let space = rest
+            .iter()
+            .position(|&b| b == b' ')
+            .ok_or(Error::InvalidArgument)?;
+        let len: usize = str::from_utf8(&rest[..space])
+            .map_err(invalid)?
+            .parse()
+            .map_err(invalid)?;
+        if len <= space + 1 || len > rest.len() {
+            return Err(Error::InvalidArgument);
+        }
+        let record = &rest[space + 1..len - 1];
+        if let Some(eq) = record.iter().position(|&b| b == b'=') {
+            if &record[..eq] == b"path" {
+                let value =
+                    str::from_utf8(&record[eq + 1..]).map_err(invalid)?;
+                path = Some(value.to_owned());
+            }
+        }
+        rest = &rest[len..];
+    }
+    Ok(path)
+}
+
+/// Push-based tar archive reader unpacking into a repo directory.
+///
+/// Regular files and directories are unpacked, links and special entries
+/// are skipped. Entries are not allowed to escape the destination, and
+/// existing files are not overwritten.
+pub struct TarImporter {
+    dest: PathBuf,
+    buf: Vec<u8>,
+    state: State,
+    long_name: Option<String>,
+    zero_blocks: usize,
+    report: UnpackReport,
+}
+
+impl TarImporter {
+    pub fn new(repo: &mut Repo, dest: &Path) -> Result<Self, Error> {
+        repo.create_dir_all(dest)?;
+        Ok(TarImporter {
+            dest: dest.to_path_buf(),
+            buf: Vec::new(),
+            state: State::Header,
+            long_name: None,
+            zero_blocks: 0,
+            report: UnpackReport::default(),
+        })
+    }
+
+    // map an entry name to repo path under destination
+    fn target(&self, name: &str) -> Result<PathBuf, Error> {
+        let mut target = self.dest.clone();
+        for comp in Path::new(name).components() {
+            match comp {
+                Component::Normal(comp) => target.push(comp),
+                Component::CurDir => {}
+                _ => return Err(Error::InvalidPath),
+            }
+        }
+        Ok(target)
+    }
+
+    // parse a header block and decide what to do with its data
+    fn start_entry(
+        &mut self,
+        repo: &mut Repo,
+        block: &[u8],
+    ) -> Result<(), Error> {
+        let header = Header::from_byte_slice(block);
+        let cksum: u32 = block[..CKSUM_OFFSET]
+            .iter()
+            .chain(&[b' '; CKSUM_LEN])
+            .chain(&block[CKSUM_OFFSET + CKSUM_LEN..])
+            .map(|&b| u32::from(b))
+            .sum();
+        if header.cksum().map_err(invalid)? != cksum {
+            return Err(Error::InvalidArgument);
+        }
+
+        let size = header.entry_size().map_err(invalid)?;
+        let name = match self.long_name.take() {
+            Some(name) => name,
+            None => str::from_utf8(&header.path_bytes())
+                .map_err(invalid)?
+                .to_owned(),
+        };
+        let entry_type = header.entry_type();
+
+        let data = if entry_type.is_gnu_longname() {
+            Data::LongName(Vec::new())
+        } else if entry_type.is_pax_local_extensions() {
+            Data::Pax(Vec::new())
+        } else if entry_type.is_pax_global_extensions() {
+            Data::Skip
+        } else if entry_type.is_file() || entry_type.is_contiguous() {
+            let target = self.target(&name)?;
+            if repo.path_exists(&target)? {
+                return Err(Error::AlreadyExists);
+            }
+            if let Some(parent) = target.parent() {
+                repo.create_dir_all(parent)?;
+            }
+            Data::File {
+                file: repo.create_file(&target)?,
+                written: 0,
+            }
+        } else if entry_type.is_dir() {
+            let target = self.target(&name)?;
+            if target != self.dest {
+                repo.create_dir_all(&target)?;
+                self.report.dirs += 1;
+            }
+            Data::Skip
+        } else {
+            self.report.skipped.push(name);
+            Data::Skip
+        };
+
+        self.state = State::Data {
+            data,
+            remaining: size,
+            padding: padding(size),
+        };
+        Ok(())
+    }
+
+    // finish data of the current entry
+    fn finish_entry(&mut self, data: Data) -> Result<(), Error> {
+        match data {
+            Data::File { mut file, written } => {
+                if written > 0 {
+                    file.finish()?;
+                }
+                self.report.files += 1;
+                self.report.bytes += written;
+            }
+            Data::LongName(name) => {
+                let name = name.split(|&b| b == 0).next().unwrap();
+                self.long_name =
+                    Some(str::from_utf8(name).map_err(invalid)?.to_owned());
+            }
+            Data::Pax(records) => {
+                if let Some(path) = pax_path(&records)? {
+                    self.long_name = Some(path);
+                }
+            }
+            Data::Skip => {}
+        }
+        Ok(())
+    }
+
+    // consume as much buffered input as possible
+    fn process(&mut self, repo: &mut Repo) -> Result<(), Error> {
+        let mut pos = 0;
+
+        loop {
+            let avail = self.buf.len() - pos;
+            let state = std::mem::replace(&mut self.state, State::Header);
+            match state {
+                State::Header => {
+                    if self.zero_blocks >= 2 {
+                        // ignore anything after the end of archive
+                        pos = self.buf.len();
+                        break;
+                    }
+                    if avail < BLOCK_SIZE {
+                        break;
+                    }
+                    let block = self.buf[pos..pos + BLOCK_SIZE].to_vec();
+                    pos += BLOCK_SIZE;
+                    if block.iter().all(|&b| b == 0) {
+                        self.zero_blocks += 1;
+                        continue;
+                    }
+                    self.zero_blocks = 0;
+                    self.start_entry(repo, &block)?;
+                }
+                State::Data {
+                    mut data,
+                    remaining,
+                    padding,
+                } => {
+                    if remaining > 0 {
+                        let len = (remaining as usize).min(avail);
+                        let chunk = &self.buf[pos..pos + len];
+                        match data {
+                            Data::File {
+                                ref mut file,
+                                ref mut written,
+                            } => {
+                                file.write_all(chunk)?;
+                                *written += len;
+                            }
+                            Data::LongName(ref mut bytes)
+                            | Data::Pax(ref mut bytes) => {
+                                bytes.extend_from_slice(chunk)
+                            }
+                            Data::Skip => {}
+                        }
+                        pos += len;
+                        self.state = State::Data {
+                            data,
+                            remaining: remaining - len as u64,
+                            padding,
+                        };
+                        if len == 0 {
+                            break;
+                        }
+                        continue;
+                    }
+
+                    let len = (padding as usize).min(avail);
+                    pos += len;
+                    if padding > len as u64 {
+                        self.state = State::Data {
+                            data,
+                            remaining,
+                            padding: padding - len as u64,
+                        };
+                        break;
+                    }
+                    self.finish_entry(data)?;
+                }
+            }
+        }
+
+        self.buf.drain(..pos);
+        Ok(())
+    }
+
+    /// Feed next chunk of the archive.
+    pub fn write(
+        &mut self,
+        repo: &mut Repo,
+        chunk: &[u8],
+    ) -> Result<(), Error> {
+        self.buf.extend_from_slice(chunk);
+        self.process(repo)
+    }
+
+    /// Finish unpacking, fails if the archive is truncated.
+    pub fn finish(mut self, repo: &mut Repo) -> Result<UnpackReport, Error> {
+        self.process(repo)?;
+        match self.state {
+            State::Header if self.buf.is_empty() => Ok(self.report),
+            _ => Err(Error::Uncompleted),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::path::{Path, PathBuf};
+
+use zbox::{Error, Repo};
+
+use changes;
+use meta;
+use stage;
+use walk;
+
+/// Options of copying a directory tree.
+#[derive(Debug, Default)]
+pub struct CopyOptions {
+    /// Replace files already existing at the target
+    pub overwrite: bool,
+
+    /// Replay all retained versions rather than copy the current content
+    pub preserve_history: bool,
+}
+
+/// Progress of a tree operation.
+#[derive(Debug)]
+pub struct Progress<'a> {
+    pub done: usize,
+    pub total: usize,
+    pub path: &'a str,
+}
+
+/// Error which stopped a tree operation.
+#[derive(Debug)]
+pub struct TransferError {
+    pub err: Error,
+
+    /// Path the operation failed on
+    pub path: String,
+
+    /// Number of entries completed before the failure
+    pub done: usize,
+}
+
+impl TransferError {
+    pub fn new(err: Error, path: &Path, done: usize) -> Self {
+        TransferError {
+            err,
+            path: path.to_str().unwrap().to_owned(),
+            done,
+        }
+    }
+}
+
+// map an error to the failure at a path
+fn fail_at<'a>(
+    path: &'a Path,
+    done: usize,
+) -> impl FnOnce(Error) -> TransferError + 'a {
+    move |err| TransferError::new(err, path, done)
+}
+
+// copy one file, either its current content or its whole history
+fn copy_file(
+    repo: &mut Repo,
+    from: &Path,
+    to: &Path,
+    opts: &CopyOptions,
+) -> Result<(), Error> {
+    let exists = repo.path_exists(to)?;
+    if exists {
+        if repo.is_dir(to)? {
+            return Err(Error::IsDir);
+        }
+        if !opts.overwrite {
+            return Err(Error::AlreadyExists);
+        }
+    }
+
+    if !opts.preserve_history {
+        repo.copy(from, to)?;
+        if !exists {
+            meta::copy_file_options(repo, from, to)?;
+        }
+        return Ok(());
+    }
+
+    // replay history into a scratch file, so an existing target is only
+    // replaced once it is complete
+    let tmp = stage::sibling(to, "copy")?;
+    stage::remove_leftover(repo, &tmp)?;
+    let hist = repo.history(from)?;
+    let mut file_opts = meta::file_options(repo, from)?;
+    let version_limit = match file_opts.version_limit {
+        Some(version_limit) => version_limit,
+        None => repo.info()?.version_limit(),
+    };
+    file_opts.version_limit = Some(version_limit.max(hist.len() as u8));
+    let src = repo.open_file(from)?;
+    for ver in hist.iter() {
+        let mut rdr = src.version_reader(ver.num())?;
+        stage::write_version_with(repo, &mut rdr, &tmp, file_opts)?;
+    }
+    repo.rename(&tmp, to)?;
+    meta::copy_file_options(repo, from, to)
+}
+
+/// Copy a directory tree to another location.
+///
+/// Target directories are created or merged into. Entries are copied in
+/// pre-order and the operation stops at the first failure. Returns the
+/// number of copied entries, not counting `from` itself.
+pub fn copy_dir_all<F>(
+    repo: &mut Repo,
+    from: &Path,
+    to: &Path,
+    opts: &CopyOptions,
+    progress: &mut F,
+) -> Result<usize, TransferError>
+where
+    F: FnMut(&Progress) -> Result<(), Error>,
+{
+    if to.starts_with(from) {
+        return Err(TransferError::new(Error::InvalidArgument, to, 0));
+    }
+    if !repo.is_dir(from).map_err(fail_at(from, 0))? {
+        return Err(TransferError::new(Error::NotDir, from, 0));
+    }
+
+    let mut ents: Vec<(PathBuf, bool)> = Vec::new();
+    walk::walk(repo, from, &mut |ent| {
+        let rel = ent.path().strip_prefix(from).unwrap().to_path_buf();
+        ents.push((rel, ent.metadata().is_dir()));
+        Ok(())
+    })
+    .map_err(fail_at(from, 0))?;
+
+    if repo.is_file(to).map_err(fail_at(to, 0))? {
+        return Err(TransferError::new(Error::NotDir, to, 0));
+    }
+    repo.create_dir_all(to).map_err(fail_at(to, 0))?;
+
+    let total = ents.len();
+    for (done, (rel, is_dir)) in ents.iter().enumerate() {
+        let src = from.join(rel);
+        let dst = to.join(rel);
+        let result = if *is_dir {
+            repo.is_file(&dst).and_then(|is_file| {
+                if is_file {
+                    Err(Error::AlreadyExists)
+                } else {
+                    repo.create_dir_all(&dst)
+                }
+            })
+        } else {
+            copy_file(repo, &src, &dst, opts)
+        };
+        result
+            .and_then(|_| {
+                progress(&Progress {
+                    done: done + 1,
+                    total,
+                    path: dst.to_str().unwrap(),
+                })
+            })
+            .map_err(fail_at(&dst, done))?;
+    }
+
+    Ok(total)
+}
+
+/// Move a file or directory tree to another location.
+///
+/// An existing target is replaced only when `overwrite` is set, it is kept
+/// if the move fails. A move is a single rename, so progress is reported
+/// once when it is done. Returns the number of moved entries, counting
+/// `from` itself.
+pub fn move_path<F>(
+    repo: &mut Repo,
+    from: &Path,
+    to: &Path,
+    overwrite: bool,
+    progress: &mut F,
+) -> Result<usize, TransferError>
+where
+    F: FnMut(&Progress) -> Result<(), Error>,
+{
+    if from == to || to.starts_with(from) {
+        return Err(TransferError::new(Error::InvalidArgument, to, 0));
+    }
+    if !repo.path_exists(from).map_err(fail_at(from, 0))? {
+        return Err(TransferError::new(Error::NotFound, from, 0));
+    }
+    let is_dir = repo.is_dir(from).map_err(fail_at(from, 0))?;
+    let mut total = 1;
+    if is_dir {
+        walk::walk(repo, from, &mut |_| {
+            total += 1;
+            Ok(())
+        })
+        .map_err(fail_at(from, 0))?;
+    }
+
+    // move an existing target aside rather than remove it, so it can be put
+    // back if the move fails
+    let mut replaced = None;
+    if repo.path_exists(to).map_err(fail_at(to, 0))? {
+        if !overwrite {
+            return Err(TransferError::new(Error::AlreadyExists, to, 0));
+        }
+        let to_is_dir = repo.is_dir(to).map_err(fail_at(to, 0))?;
+        let aside = stage::sibling(to, "replaced")
+            .and_then(|aside| {
+                if repo.is_dir(&aside)? {
+                    repo.remove_dir_all(&aside)?;
+                }
+                stage::remove_leftover(repo, &aside)?;
+                repo.rename(to, &aside)?;
+                Ok(aside)
+            })
+            .map_err(fail_at(to, 0))?;
+        replaced = Some((aside, to_is_dir));
+    }
+
+    let from_str = from.to_str().unwrap();
+    let to_str = to.to_str().unwrap();
+    if let Err(err) = repo.rename(from, to) {
+        if let Some((aside, _)) = replaced {
+            let _ = repo.rename(&aside, to);
+        }
+        return Err(TransferError::new(err, from, 0));
+    }
+    // the move is done, so removing the replaced target is best-effort, a
+    // leftover is cleaned up by the next move to the same target
+    if let Some((aside, to_is_dir)) = replaced {
+        let _ = if to_is_dir {
+            repo.remove_dir_all(&aside)
+        } else {
+            repo.remove_file(&aside)
+        };
+        changes::record_removal(repo, to_str, to_is_dir, None);
+    }
+    changes::record_removal(repo, from_str, is_dir, Some(to_str));
+    progress(&Progress {
+        done: total,
+        total,
+        path: to_str,
+    })
+    .map_err(|err| TransferError::new(err, to, total))?;
+
+    Ok(total)
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use zbox::{Error, Repo};
+
+use hash::{self, HashAlgo};
+use walk;
+
+// algorithm used for content comparison
+const CONTENT_HASH: HashAlgo = HashAlgo::Blake2b;
+
+/// Listing of a directory tree, keyed by path relative to its root.
+pub type TreeListing = BTreeMap<String, Entry>;
+
+/// An entry in a tree listing.
+#[derive(Debug)]
+pub struct Entry {
+    pub is_dir: bool,
+    pub content_len: usize,
+}
+
+/// Differences of tree B relative to tree A.
+#[derive(Debug, Default)]
+pub struct TreeDiff {
+    /// Entries only in tree B
+    pub added: Vec<String>,
+
+    /// Entries only in tree A
+    pub removed: Vec<String>,
+
+    /// Entries in both trees but of different type, length or content
+    pub changed: Vec<String>,
+}
+
+/// List all entries under a directory.
+pub fn list(repo: &Repo, root: &Path) -> Result<TreeListing, Error> {
+    if !repo.is_dir(root)? {
+        return Err(Error::NotDir);
+    }
+    let mut listing = TreeListing::new();
+    walk::walk(repo, root, &mut |ent| {
+        let rel = ent.path().strip_prefix(root).unwrap();
+        let md = ent.metadata();
+        listing.insert(
+            rel.to_str().unwrap().to_owned(),
+            Entry {
+                is_dir: md.is_dir(),
+                content_len: md.content_len(),
+            },
+        );
+        Ok(())
+    })?;
+    Ok(listing)
+}
+
+/// Compare two tree listings by entry type and content length.
+///
+/// Returns the differences and relative paths of files which exist in
+/// both trees with the same length, whose content has to be compared to
+/// tell if they are changed.
+pub fn compare(a: &TreeListing, b: &TreeListing) -> (TreeDiff, Vec<String>) {
+    let mut diff = TreeDiff::default();
+    let mut same_len = Vec::new();
+    for (path, ent_a) in a.iter() {
+        match b.get(path) {
+            None => diff.removed.push(path.clone()),
+            Some(ent_b) if ent_a.is_dir != ent_b.is_dir => {
+                diff.changed.push(path.clone())
+            }
+            Some(_) if ent_a.is_dir => {}
+            Some(ent_b) if ent_a.content_len != ent_b.content_len => {
+                diff.changed.push(path.clone())
+            }
+            Some(_) => same_len.push(path.clone()),
+        }
+    }
+    diff.added = b
+        .keys()
+        .filter(|path| !a.contains_key(*path))
+        .cloned()
+        .collect();
+    (diff, same_len)
+}
+
+/// Hash content of files under a directory.
+pub fn hash_files(
+    repo: &mut Repo,
+    root: &Path,
+    paths: &[String],
+) -> Result<Vec<String>, Error> {
+    paths
+        .iter()
+        .map(|path| {
+            let mut file = repo.open_file(root.join(path))?;
+            hash::hash_reader(CONTENT_HASH, &mut file)
+        })
+        .collect()
+}
+
+/// Add files with different content hashes to changed entries.
+pub fn add_content_changes(
+    diff: &mut TreeDiff,
+    paths: Vec<String>,
+    hashes_a: &[String],
+    hashes_b: &[String],
+) {
+    for ((path, hash_a), hash_b) in
+        paths.into_iter().zip(hashes_a).zip(hashes_b)
+    {
+        if hash_a != hash_b {
+            diff.changed.push(path);
+        }
+    }
+    diff.changed.sort();
+}
@@ -0,0 +1,137 @@
+use std::io::Read;
+use std::path::Path;
+
+use zbox::{Error, Repo};
+
+use transfer::Progress;
+use walk;
+
+// size of buffer used to read through content
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// An entry which failed verification.
+#[derive(Debug)]
+pub struct Problem {
+    pub path: String,
+
+    /// Version number, not set if the failure is not specific to a version
+    pub version: Option<usize>,
+
+    pub err: Error,
+}
+
+/// Summary of a verification pass.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub files: usize,
+    pub dirs: usize,
+    pub versions: usize,
+    pub bytes: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl VerifyReport {
+    fn problem(&mut self, path: &Path, version: Option<usize>, err: Error) {
+        self.problems.push(Problem {
+            path: path.to_str().unwrap().to_owned(),
+            version,
+            err,
+        });
+    }
+}
+
+// read through all content, check its length is as expected
+fn read_through<R: Read>(
+    rdr: &mut R,
+    buf: &mut [u8],
+    expected: usize,
+) -> Result<usize, Error> {
+    let mut len = 0;
+    loop {
+        match rdr.read(buf)? {
+            0 => break,
+            read => len += read,
+        }
+    }
+    if len != expected {
+        return Err(Error::Corrupted);
+    }
+    Ok(len)
+}
+
+// verify current content of a file, or all of its versions
+fn verify_file(
+    repo: &mut Repo,
+    path: &Path,
+    all_versions: bool,
+    buf: &mut [u8],
+    report: &mut VerifyReport,
+) -> Result<(), Error> {
+    let mut file = repo.open_file(path)?;
+    let md = file.metadata()?;
+    let read = read_through(&mut file, buf, md.content_len())?;
+    report.bytes += read;
+
+    if all_versions {
+        for ver in file.history()? {
+            let result = file.version_reader(ver.num()).and_then(|mut rdr| {
+                read_through(&mut rdr, buf, ver.content_len())
+            });
+            match result {
+                Ok(read) => {
+                    report.versions += 1;
+                    report.bytes += read;
+                }
+                Err(err) => report.problem(path, Some(ver.num()), err),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read through every file under a directory to check it is intact.
+///
+/// Current content of each file, and every retained version with
+/// `all_versions`, is fully read and its length compared with its metadata.
+/// Failures to read a file are recorded in the report and don't stop the
+/// pass, while failing to list the tree or an error from the progress
+/// callback does.
+pub fn verify<F>(
+    repo: &mut Repo,
+    path: &Path,
+    all_versions: bool,
+    progress: &mut F,
+) -> Result<VerifyReport, Error>
+where
+    F: FnMut(&Progress) -> Result<(), Error>,
+{
+    let mut report = VerifyReport::default();
+    let mut files = Vec::new();
+    if repo.is_dir(path)? {
+        walk::walk(repo, path, &mut |ent| {
+            if ent.metadata().is_dir() {
+                report.dirs += 1;
+            } else {
+                files.push(ent.path().to_path_buf());
+            }
+            Ok(())
+        })?;
+    } else {
+        files.push(path.to_path_buf());
+    }
+
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let total = files.len();
+    for (done, file) in files.iter().enumerate() {
+        match verify_file(repo, file, all_versions, &mut buf, &mut report) {
+            Ok(_) => report.files += 1,
+            Err(err) => report.problem(file, None, err),
+        }
+        progress(&Progress {
+            done: done + 1,
+            total,
+            path: file.to_str().unwrap(),
+        })?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,197 @@
+use std::path::{Path, PathBuf};
+
+use zbox::{DirEntry, Error, Repo};
+
+use meta;
+
+/// Recursively visit every entry under a directory in pre-order.
+///
+/// The visitor is called for each entry before descending into it. The
+/// binding-managed metadata directory is skipped.
+pub fn walk<F>(repo: &Repo, path: &Path, visit: &mut F) -> Result<(), Error>
+where
+    F: FnMut(&DirEntry) -> Result<(), Error>,
+{
+    for ent in repo.read_dir(path)? {
+        if meta::is_meta_path(ent.path()) {
+            continue;
+        }
+        visit(&ent)?;
+        if ent.metadata().is_dir() {
+            walk(repo, ent.path(), visit)?;
+        }
+    }
+    Ok(())
+}
+
+/// Collect paths of all regular files under a directory.
+pub fn file_paths(repo: &Repo, path: &Path) -> Result<Vec<String>, Error> {
+    let mut paths = Vec::new();
+    walk(repo, path, &mut |ent| {
+        if ent.metadata().is_file() {
+            paths.push(ent.path().to_str().unwrap().to_owned());
+        }
+        Ok(())
+    })?;
+    Ok(paths)
+}
+
+/// Order in which a directory is yielded relative to its children.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WalkOrder {
+    Pre,
+    Post,
+}
+
+/// Options of a streaming walk.
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    pub max_depth: Option<usize>,
+    pub files_only: bool,
+    pub dirs_only: bool,
+    pub order: WalkOrder,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        WalkOptions {
+            max_depth: None,
+            files_only: false,
+            dirs_only: false,
+            order: WalkOrder::Pre,
+        }
+    }
+}
+
+// one directory level being walked
+struct Frame {
+    // remaining entries, in reverse order so pop gives the next one
+    entries: Vec<DirEntry>,
+    depth: usize,
+
+    // directory held back until its children are done, for post-order
+    dir: Option<DirEntry>,
+}
+
+/// Streaming recursive directory walker.
+///
+/// Only one directory level is read at a time, entries are produced one by
+/// one on each call to `next`. Depth of the direct children of the root is 1.
+pub struct Walker {
+    root: PathBuf,
+    opts: WalkOptions,
+    stack: Vec<Frame>,
+    started: bool,
+
+    // whether children of the last returned entry have been queued
+    descended: bool,
+}
+
+impl Walker {
+    pub fn new(root: &Path, opts: WalkOptions) -> Result<Self, Error> {
+        if opts.files_only && opts.dirs_only {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(Walker {
+            root: root.to_path_buf(),
+            opts,
+            stack: Vec::new(),
+            started: false,
+            descended: false,
+        })
+    }
+
+    fn push_dir(
+        &mut self,
+        repo: &Repo,
+        path: &Path,
+        depth: usize,
+        dir: Option<DirEntry>,
+    ) -> Result<(), Error> {
+        let mut entries = repo.read_dir(path)?;
+        entries.retain(|ent| !meta::is_meta_path(ent.path()));
+        entries.reverse();
+        self.stack.push(Frame {
+            entries,
+            depth,
+            dir,
+        });
+        Ok(())
+    }
+
+    fn accepts(&self, ent: &DirEntry) -> bool {
+        let md = ent.metadata();
+        !(self.opts.files_only && !md.is_file()
+            || self.opts.dirs_only && !md.is_dir())
+    }
+
+    /// Get the next entry and its depth, or `None` when the walk is done.
+    pub fn next(
+        &mut self,
+        repo: &Repo,
+    ) -> Result<Option<(DirEntry, usize)>, Error> {
+        self.descended = false;
+        if !self.started {
+            self.started = true;
+            if self.opts.max_depth != Some(0) {
+                let root = self.root.clone();
+                self.push_dir(repo, &root, 1, None)?;
+            }
+        }
+
+        loop {
+            let next = match self.stack.last_mut() {
+                Some(frame) => {
+                    frame.entries.pop().map(|ent| (ent, frame.depth))
+                }
+                None => return Ok(None),
+            };
+
+            match next {
+                Some((ent, depth)) => {
+                    let descend = ent.metadata().is_dir()
+                        && self.opts.max_depth.map_or(true, |max| depth < max);
+                    if !descend {
+                        if self.accepts(&ent) {
+                            return Ok(Some((ent, depth)));
+                        }
+                        continue;
+                    }
+
+                    let path = ent.path().to_path_buf();
+                    match self.opts.order {
+                        WalkOrder::Pre => {
+                            self.push_dir(repo, &path, depth + 1, None)?;
+                            if self.accepts(&ent) {
+                                self.descended = true;
+                                return Ok(Some((ent, depth)));
+                            }
+                        }
+                        WalkOrder::Post => {
+                            self.push_dir(repo, &path, depth + 1, Some(ent))?;
+                        }
+                    }
+                }
+                None => {
+                    let frame = self.stack.pop().unwrap();
+                    if let Some(dir) = frame.dir {
+                        if self.accepts(&dir) {
+                            return Ok(Some((dir, frame.depth - 1)));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Do not descend into the directory just returned by `next`.
+    ///
+    /// Only effective in pre-order, as in post-order a directory is returned
+    /// after its children.
+    pub fn skip_dir(&mut self) {
+        if self.descended {
+            self.stack.pop();
+            self.descended = false;
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use zip_lib::result::ZipError;
+use zip_lib::write::SimpleFileOptions;
+use zip_lib::{AesMode, CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+use zbox::{Error, Repo};
+
+use tar::UnpackReport;
+use time_to_f64;
+use walk;
+
+// size of chunks streamed between repo files and archive
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// seconds in a day
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+// largest archive built in memory, which is returned as a JS ArrayBuffer
+const MAX_ARCHIVE_SIZE: u64 = u32::MAX as u64;
+
+// in-memory archive which fails to grow past MAX_ARCHIVE_SIZE
+struct Archive(Cursor<Vec<u8>>);
+
+impl Write for Archive {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.0.position() + buf.len() as u64 > MAX_ARCHIVE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Zip archive is too large",
+            ));
+        }
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Seek for Archive {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+fn zip_error(err: ZipError) -> Error {
+    match err {
+        ZipError::Io(err) => Error::from(err),
+        ZipError::InvalidPassword => Error::Decrypt,
+        _ => Error::InvalidArgument,
+    }
+}
+
+// convert seconds since epoch to zip date time, clamped to its 1980 start
+fn zip_time(secs: f64) -> DateTime {
+    let secs = secs as i64;
+    let (days, secs) = (secs.div_euclid(DAY_SECS), secs.rem_euclid(DAY_SECS));
+
+    // civil date from days since epoch
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    DateTime::from_date_and_time(
+        year as u16,
+        month as u8,
+        day as u8,
+        (secs / 3600) as u8,
+        (secs % 3600 / 60) as u8,
+        (secs % 60) as u8,
+    )
+    .unwrap_or_default()
+}
+
+/// Options of creating a zip archive.
+#[derive(Debug, Default)]
+pub struct ZipOptions {
+    /// Deflate compression level, 0 stores entries without compression
+    pub compression_level: Option<i64>,
+
+    /// Encrypt entries with AES-256 using this password
+    pub password: Option<String>,
+}
+
+/// Build a zip archive of a repo file or directory tree in memory.
+///
+/// Entry names are relative to the archived path, and entry times are taken
+/// from file modification times. The whole archive is held in memory, and
+/// copied once more when returned to JS, so it is limited to 4 GiB and fails
+/// with an error as soon as it grows past that.
+pub fn export_zip(
+    repo: &mut Repo,
+    path: &Path,
+    opts: &ZipOptions,
+) -> Result<Vec<u8>, Error> {
+    let mut entries: Vec<(PathBuf, String, bool)> = Vec::new();
+    if repo.is_dir(path)? {
+        walk::walk(repo, path, &mut |ent| {
+            let rel = ent.path().strip_prefix(path).unwrap();
+            entries.push((
+                ent.path().to_path_buf(),
+                rel.to_str().unwrap().to_owned(),
+                ent.metadata().is_dir(),
+            ));
+            Ok(())
+        })?;
+    } else {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(Error::InvalidPath)?;
+        entries.push((path.to_path_buf(), name.to_owned(), false));
+    }
+
+    let mut options = SimpleFileOptions::default();
+    options = match opts.compression_level {
+        Some(0) => options.compression_method(CompressionMethod::Stored),
+        level => options
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(level),
+    };
+
+    let mut writer = ZipWriter::new(Archive(Cursor::new(Vec::new())));
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    for (ent_path, name, is_dir) in entries {
+        let md = repo.metadata(&ent_path)?;
+        let mut options = options
+            .last_modified_time(zip_time(time_to_f64(md.modified_at())))
+            .large_file(md.content_len() as u64 >= u64::from(u32::MAX));
+        if let Some(ref pwd) = opts.password {
+            options = options.with_aes_encryption(AesMode::Aes256, pwd);
+        }
+
+        if is_dir {
+            writer.add_directory(name, options).map_err(zip_error)?;
+            continue;
+        }
+
+        writer.start_file(name, options).map_err(zip_error)?;
+        let mut file = repo.open_file(&ent_path)?;
+        loop {
+            let read = file.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buf[..read])?;
+        }
+    }
+
+    let archive = writer.finish().map_err(zip_error)?;
+    Ok(archive.0.into_inner())
+}
+
+/// Unpack a zip archive into a repo directory.
+///
+/// Entries are not allowed to escape the destination, and existing files
+/// are not overwritten. Encrypted entries need the password.
+pub fn import_zip(
+    repo: &mut Repo,
+    data: &[u8],
+    dest: &Path,
+    password: Option<&str>,
+) -> Result<UnpackReport, Error> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(zip_error)?;
+    let mut report = UnpackReport::default();
+    repo.create_dir_all(dest)?;
+
+    for idx in 0..archive.len() {
+        let mut ent = match password {
+            Some(pwd) => archive.by_index_decrypt(idx, pwd.as_bytes()),
+            None => archive.by_index(idx),
+        }
+        .map_err(zip_error)?;
+
+        let rel = ent.enclosed_name().ok_or(Error::InvalidPath)?;
+        let target = dest.join(rel);
+        if ent.is_dir() {
+            repo.create_dir_all(&target)?;
+            report.dirs += 1;
+            continue;
+        }
+        if !ent.is_file() {
+            report.skipped.push(ent.name().to_owned());
+            continue;
+        }
+
+        if repo.path_exists(&target)? {
+            return Err(Error::AlreadyExists);
+        }
+        if let Some(parent) = target.parent() {
+            repo.create_dir_all(parent)?;
+        }
+        let mut file = repo.create_file(&target)?;
+        let written = io::copy(&mut ent, &mut file)? as usize;
+        if written > 0 {
+            file.finish()?;
+        }
+        report.files += 1;
+        report.bytes += written;
+    }
+
+    Ok(report)
+}
@@ -140,6 +140,80 @@ describe('Repo Open/Close Test', function() {
     await repo.close();
   });
 
+  it('should check super block without repair', async function() {
+    if (!isNodeJs) return;
+
+    let result = await zbox.checkSuperBlock({ uri, pwd });
+    expect(result.supported).to.be.true;
+    expect(result.consistent).to.be.true;
+    expect(result.copies).to.have.lengthOf(2);
+    result.copies.forEach(copy => {
+      expect(copy.present).to.be.true;
+      expect(copy.readable).to.be.true;
+      expect(copy.seq).to.equal(result.copies[0].seq);
+      expect(copy.error).to.be.null;
+    });
+
+    // the repo lock is not taken, so an open repo can be checked
+    const opened = await zbox.openRepo({ uri, pwd });
+    result = await zbox.checkSuperBlock({ uri, pwd });
+    expect(result.consistent).to.be.true;
+    await opened.close();
+
+    result = await zbox.checkSuperBlock({ uri, pwd: 'wrong pwd' });
+    expect(result.consistent).to.be.false;
+    result.copies.forEach(copy => {
+      expect(copy.present).to.be.true;
+      expect(copy.readable).to.be.false;
+      expect(copy.seq).to.be.null;
+      expect(copy.error).to.be.a('string');
+    });
+
+    result = await zbox.checkSuperBlock({
+      uri: 'file://./tt/non-exist-repo',
+      pwd
+    });
+    expect(result.consistent).to.be.false;
+    result.copies.forEach(copy => {
+      expect(copy.present).to.be.false;
+      expect(copy.readable).to.be.false;
+    });
+    await expectError(zbox.checkSuperBlock({ uri }));
+
+    // other storages are reported as unsupported rather than failing
+    result = await zbox.checkSuperBlock({ uri: 'mem://foo', pwd });
+    expect(result.supported).to.be.false;
+    expect(result.consistent).to.be.null;
+    expect(result.copies).to.be.empty;
+  });
+
+  it('should verify repo password', async function() {
+    if (!isNodeJs) return;
+
+    expect(await zbox.verifyPassword({ uri, pwd })).to.be.true;
+    expect(await zbox.verifyPassword({ uri, pwd: 'wrong pwd' })).to.be.false;
+
+    // the repo lock is not taken, so it works while the repo is open
+    const opened = await zbox.openRepo({ uri, pwd });
+    expect(await zbox.verifyPassword({ uri, pwd })).to.be.true;
+    await opened.close();
+
+    // storage not readable locally is verified by opening the repo
+    const memUri = `mem://verify-${Date.now()}`;
+    const memRepo = await zbox.openRepo({
+      uri: memUri,
+      pwd,
+      opts: { create: true }
+    });
+    await memRepo.close();
+    expect(await zbox.verifyPassword({ uri: memUri, pwd })).to.be.true;
+    expect(await zbox.verifyPassword({ uri: memUri, pwd: 'wrong pwd' }))
+      .to.be.false;
+
+    await expectError(zbox.verifyPassword({ uri, pwd: 123 }));
+    await expectError(zbox.verifyPassword({ uri: 'wrong uri', pwd }));
+  });
+
   it('should exit zbox', async function() {
     if (zbox) await zbox.exit();
   });
@@ -364,6 +438,147 @@ describe('File IO Test', function() {
     await file.close();
   });
 
+  it(`should get version of version reader`, async function() {
+    let vrdr = await repo.openVersion(filePath, 2);
+    let ver = await vrdr.version();
+    expect(ver.num).to.equal(2);
+    expect(ver.contentLen).to.equal(3);
+    expect(ver.createdAt).to.be.a('number');
+    await vrdr.close();
+  });
+
+  it(`should read version as stream in chunks (Node.js)`, function(done) {
+    if (!isNodeJs) return done();
+
+    let vrdr;
+    let chunks = [];
+
+    repo.openVersion(filePath, 2)
+      .then(v => {
+        vrdr = v;
+        return vrdr.readStream({ chunkSize: 2 });
+      })
+      .then(rdr => {
+        rdr.on('data', (chunk) => chunks.push(chunk));
+        rdr.on('end', async () => {
+          expect(chunks.length).to.equal(2);
+          expect(Buffer.concat(chunks)).to.eql(Buffer.from(buf));
+          await vrdr.close();
+          done();
+        });
+        rdr.on('error', async (err) => {
+          await vrdr.close();
+          done(err);
+        });
+      });
+  });
+
+  it(`should not read version stream with bad chunk size (Node.js)`, async function() {
+    if (!isNodeJs) return;
+
+    const vrdr = await repo.openVersion(filePath, 2);
+    await expectError(vrdr.readStream({ chunkSize: -1 }));
+    await expectError(vrdr.readStream({ chunkSize: 1.5 }));
+    await expectError(vrdr.readStream({ chunkSize: Infinity }));
+    await expectError(vrdr.readStream({ chunkSize: 1024 * 1024 * 1024 }));
+    await vrdr.close();
+  });
+
+  it(`should open version directly from repo`, async function() {
+    let vrdr = await repo.openVersion(filePath, 2);
+    let result = await vrdr.readAll();
+    expect(result).to.eql(buf);
+    await vrdr.close();
+
+    await expectError(repo.openVersion(filePath, 100));
+    await expectError(repo.openVersion(filePath));
+  });
+
+  it(`should open version at a point in time`, async function() {
+    let vrdr = await repo.openVersionAt(filePath, new Date());
+    let result = await vrdr.readAll();
+    expect(result).to.eql(buf2);
+    await vrdr.close();
+
+    await expectError(repo.openVersionAt(filePath, 0));
+    await expectError(repo.openVersionAt(filePath, 'now'));
+  });
+
+  it(`should diff two binary versions`, async function() {
+    const diff = await repo.diffVersions(filePath, 2, 3);
+    expect(diff.kind).to.equal('binary');
+    expect(diff.lenA).to.equal(3);
+    expect(diff.lenB).to.equal(3);
+    expect(diff.ranges).to.eql([{ offset: 0, len: 3 }]);
+
+    await expectError(repo.diffVersions(filePath, 2));
+  });
+
+  it(`should diff two text versions`, async function() {
+    const path = `/${Date.now()}.txt`;
+    let file = await repo.createFile(path);
+    const enc = new TextEncoder();
+    await file.writeOnce(enc.encode('foo\nbar\n'));
+    await file.seek({ from: Zbox.SeekFrom.Start, offset: 0 });
+    await file.writeOnce(enc.encode('baz\n'));
+    const ver = await file.currVersion();
+    await file.close();
+
+    const diff = await repo.diffVersions(path, ver - 1, ver);
+    expect(diff.kind).to.equal('text');
+    expect(diff.unified).to.have.string('-foo');
+    expect(diff.unified).to.have.string('+baz');
+  });
+
+  it(`should annotate version on finish`, async function() {
+    const path = `/${Date.now()}.note`;
+    let file = await repo.createFile(path);
+    await file.write(new Uint8Array([1, 2]));
+    await file.finish({ message: 'first draft', author: 'tester' });
+    const ver = await file.currVersion();
+
+    let hist = await file.history();
+    expect(hist[hist.length - 1].message).to.equal('first draft');
+    expect(hist[hist.length - 1].author).to.equal('tester');
+    expect(hist[0].labels).to.eql([]);
+    await file.close();
+
+    await repo.labelVersion(path, ver, 'v1.0');
+    await repo.labelVersion(path, ver, 'v1.0');
+    hist = await repo.history(path);
+    expect(hist[hist.length - 1].labels).to.eql(['v1.0']);
+
+    await expectError(repo.labelVersion(path, ver + 1, 'v2.0'));
+    await expectError(repo.labelVersion(path, ver));
+
+    // notes move along with rename and are dropped on removal
+    const newPath = `${path}.moved`;
+    await repo.rename({ from: path, to: newPath });
+    hist = await repo.history(newPath);
+    expect(hist[hist.length - 1].labels).to.eql(['v1.0']);
+    await repo.removeFile(newPath);
+    file = await repo.createFile(newPath);
+    await file.writeOnce(new Uint8Array([1, 2]));
+    await file.close();
+    hist = await repo.history(newPath);
+    expect(hist.every(v => v.labels.length === 0)).to.be.true;
+    expect(hist.every(v => !v.message)).to.be.true;
+    await repo.removeFile(newPath);
+
+    // notes of files under a renamed dir move along too
+    const dir = `${path}.dir`;
+    await repo.createDir(dir);
+    file = await repo.createFile(`${dir}/inner`);
+    await file.writeOnce(new Uint8Array([1]));
+    await file.close();
+    const innerVer = (await repo.history(`${dir}/inner`)).pop().num;
+    await repo.labelVersion(`${dir}/inner`, innerVer, 'inner');
+    await repo.rename({ from: dir, to: `${dir}.moved` });
+    hist = await repo.history(`${dir}.moved/inner`);
+    expect(hist[hist.length - 1].labels).to.eql(['inner']);
+    await repo.removeDirAll(`${dir}.moved`);
+  });
+
   it(`should able to seek in file`, async function() {
     let file = await repo.openFile({ path: filePath, opts: { read: true } });
     let newPos = await file.seek({ from: Zbox.SeekFrom.Start, offset: 1 });
@@ -471,6 +686,37 @@ describe('File IO Test', function() {
     await file.close();
   });
 
+  it('should hash file and version content', async function() {
+    const path = `/${Date.now()}.hash`;
+    const sha256 =
+      'c3ab8ff13720e8ad9047dd39466b3c8974e592c2fa383d4a3960714caef0c4f2';
+    const blake2b =
+      '8df31f60d6aeabd01b7dc83f277d0e24cbe104f7290ff89077a7eb58646068ed' +
+      'fe1a83022866c46f65fb91612e516e0ecfa5cb25fc16b37d2c8d73732fe74cb2';
+
+    let file = await repo.createFile(path);
+    await file.writeOnce('foo');
+    await file.writeOnce('bar');
+    expect(await file.hash()).to.equal(sha256);
+    expect(await file.hash('blake2b')).to.equal(blake2b);
+    expect(await repo.hashFile(path)).to.equal(sha256);
+    expect(await repo.hashFile(path, 'blake2b')).to.equal(blake2b);
+
+    // hashing doesn't move the reader position
+    const vrdr = await file.versionReader((await file.currVersion()) - 1);
+    await vrdr.seek({ from: Zbox.SeekFrom.Start, offset: 1 });
+    expect(await vrdr.hash('sha256')).to.equal(
+      '2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae'
+    );
+    expect(await vrdr.readAllString()).to.equal('oo');
+    await vrdr.close();
+
+    await expectError(file.hash('md5'));
+    await expectError(repo.hashFile('/non-exist'));
+    await file.close();
+    await expectError(file.hash());
+  });
+
   after(async function() {
     if (repo) await repo.close();
     if (zbox) await zbox.exit();
@@ -550,6 +796,159 @@ describe('Dir IO Test', function() {
     expect(ent.metadata).to.be.an('object');
   });
 
+  it('should read dir in sorted pages', async function() {
+    const base = `/page-${Date.now()}`;
+    await repo.createDir(base);
+    for (const [name, len] of [['b', 3], ['c', 1], ['a', 2]]) {
+      const file = await repo.createFile(`${base}/${name}`);
+      await file.writeOnce(new Uint8Array(len));
+      await file.close();
+    }
+
+    let page = await repo.readDir(base, { limit: 2 });
+    expect(page.entries.map(e => e.fileName)).to.eql(['a', 'b']);
+    expect(page.entries[0].metadata).to.be.undefined;
+    expect(page.cursor).to.be.a('string');
+    page = await repo.readDir(base, { limit: 2, cursor: page.cursor });
+    expect(page.entries.map(e => e.fileName)).to.eql(['c']);
+    expect(page.cursor).to.be.null;
+
+    page = await repo.readDir(base, {
+      sortBy: 'size', order: 'desc', offset: 1, withMetadata: true
+    });
+    expect(page.entries.map(e => e.fileName)).to.eql(['a', 'c']);
+    expect(page.entries[0].metadata.contentLen).to.equal(2);
+
+    await expectError(repo.readDir(base, { sortBy: 'color' }));
+    await expectError(repo.readDir(base, { cursor: 'bogus' }));
+    page = await repo.readDir(base, { limit: 1 });
+    await expectError(
+      repo.readDir(base, { offset: 1, cursor: page.cursor })
+    );
+    await repo.removeDirAll(base);
+  });
+
+  it('should compute disk usage', async function() {
+    const base = `/du-${Date.now()}`;
+    await repo.createDirAll(`${base}/sub`);
+    let file = await repo.createFile(`${base}/small`);
+    await file.writeOnce(new Uint8Array(2));
+    await file.close();
+    file = await repo.createFile(`${base}/sub/big`);
+    await file.writeOnce(new Uint8Array(5));
+    await file.close();
+    file = await repo.openFile({ path: `${base}/sub/big`, opts: { write: true } });
+    await file.writeOnce(new Uint8Array(8));
+    await file.close();
+
+    const usage = await repo.du(base, { top: 1 });
+    expect(usage.totalBytes).to.equal(10);
+    expect(usage.historyBytes).to.be.at.least(15);
+    expect(usage.fileCount).to.equal(2);
+    expect(usage.dirCount).to.equal(1);
+    expect(usage.versionCount).to.be.at.least(3);
+    expect(usage.largestFiles).to.eql([{ path: `${base}/sub/big`, size: 8 }]);
+    expect(usage.subtrees.length).to.equal(1);
+    expect(usage.subtrees[0].path).to.equal(`${base}/sub`);
+    expect(usage.subtrees[0].totalBytes).to.equal(8);
+
+    await expectError(repo.du(`${base}/small`));
+    await repo.removeDirAll(base);
+  });
+
+  it('should verify repo integrity', async function() {
+    const path = `${dirPath}-verify`;
+    await repo.createDirAll(`${path}/sub`);
+    const file = await repo.createFile(`${path}/sub/a.txt`);
+    await file.writeOnce('foo');
+    await file.writeOnce('bar');
+    await file.close();
+
+    const events = [];
+    let report = await repo.verify({ path, onProgress: p => events.push(p) });
+    expect(report.files).to.equal(1);
+    expect(report.dirs).to.equal(1);
+    expect(report.versions).to.equal(0);
+    expect(report.bytes).to.equal(6);
+    expect(report.problems).to.eql([]);
+    expect(events).to.eql([{ done: 1, total: 1, path: `${path}/sub/a.txt` }]);
+
+    report = await repo.verify({ path, allVersions: true });
+    const hist = await repo.history(`${path}/sub/a.txt`);
+    expect(report.versions).to.equal(hist.length);
+    expect(report.problems).to.eql([]);
+
+    report = await repo.verify();
+    expect(report.problems).to.eql([]);
+    await expectError(repo.verify({ path: '/non-exist' }));
+    await expectError(repo.verify({
+      path,
+      onProgress: () => { throw new Error('stop'); }
+    }));
+  });
+
+  it('should walk dir recursively', async function() {
+    const collect = async (path, opts) => {
+      const ents = [];
+      for await (const ent of repo.walk(path, opts)) {
+        ents.push(ent);
+      }
+      return ents;
+    };
+
+    let ents = await collect('/1');
+    expect(ents.map(e => e.path)).to.eql(['/1/2', '/1/2/3', dirPath2]);
+    expect(ents.map(e => e.depth)).to.eql([1, 2, 3]);
+    expect(ents[0].fileName).to.equal('2');
+    expect(ents[0].metadata).to.be.an('object');
+
+    ents = await collect('/1', { followOrder: 'post' });
+    expect(ents.map(e => e.path)).to.eql([dirPath2, '/1/2/3', '/1/2']);
+
+    ents = await collect('/1', { maxDepth: 2 });
+    expect(ents.map(e => e.path)).to.eql(['/1/2', '/1/2/3']);
+
+    ents = await collect('/1', { filesOnly: true });
+    expect(ents.length).to.equal(0);
+    ents = await collect('/1', { dirsOnly: true });
+    expect(ents.length).to.equal(3);
+
+    await expectError(collect('/non-exist'));
+    await expectError(collect('/1', { followOrder: 'in' }));
+    await expectError(collect('/1', { filesOnly: true, dirsOnly: true }));
+  });
+
+  it('should match and remove paths by glob', async function() {
+    const base = `/glob-${Date.now()}`;
+    await repo.createDirAll(`${base}/sub`);
+    await repo.createDirAll(`${base}/build`);
+    for (const path of ['a.md', 'b.txt', 'c.bin', 'sub/d.md', 'build/e.md']) {
+      const file = await repo.createFile(`${base}/${path}`);
+      await file.close();
+    }
+
+    let ents = await repo.glob(`${base}/*.{md,txt}`);
+    expect(ents.map(e => e.path)).to.have.members([
+      `${base}/a.md`, `${base}/b.txt`
+    ]);
+    expect(ents[0].metadata).to.be.an('object');
+
+    ents = await repo.glob(`${base}/**/*.md`, { ignore: [`${base}/build`] });
+    expect(ents.map(e => e.path)).to.have.members([
+      `${base}/a.md`, `${base}/sub/d.md`
+    ]);
+
+    await expectError(repo.glob('relative/*'));
+    await expectError(repo.glob(`${base}/[`));
+    await expectError(repo.glob(`${base}/*`, { ignore: [1] }));
+
+    const removed = await repo.removeGlob(`${base}/{build,*.bin}`);
+    expect(removed).to.have.members([`${base}/build`, `${base}/c.bin`]);
+    expect(await repo.pathExists(`${base}/build/e.md`)).to.be.false;
+    expect(await repo.pathExists(`${base}/a.md`)).to.be.true;
+    await repo.removeDirAll(base);
+  });
+
   it('should not remove root dir', async function() {
     await expectError(repo.removeDir('/'));
   });
@@ -594,6 +993,683 @@ describe('Dir IO Test', function() {
   });
 });
 
+// ============================================
+// Snapshot Test
+// ============================================
+describe('Snapshot Test', function() {
+  let repo, dirPath, filePath, snapName;
+
+  this.timeout(TIMEOUT);
+
+  before(async function() {
+    dirPath = `/snap-${Date.now()}`;
+    filePath = `${dirPath}/file`;
+    snapName = `snap-${Date.now()}`;
+    await zbox.initEnv({ log: { level: 'debug' } });
+    repo = await zbox.openRepo({ uri, pwd, opts: { create: true }});
+    await repo.createDir(dirPath);
+    let file = await repo.createFile(filePath);
+    await file.writeOnce(new Uint8Array([1, 2, 3]));
+    await file.close();
+  });
+
+  it('should not create snapshot with wrong name', async function() {
+    await expectError(repo.createSnapshot());
+    await expectError(repo.createSnapshot(''));
+    await expectError(repo.createSnapshot('a/b'));
+  });
+
+  it('should create snapshot', async function() {
+    const info = await repo.createSnapshot(snapName);
+    expect(info.name).to.equal(snapName);
+    expect(info.createdAt).to.be.a('number');
+    expect(info.fileCount).to.be.at.least(1);
+    expect(info.dirCount).to.be.at.least(1);
+    await expectError(repo.createSnapshot(snapName));
+  });
+
+  it('should list snapshots', async function() {
+    const list = await repo.listSnapshots();
+    expect(list.map(s => s.name)).to.include(snapName);
+  });
+
+  it('should not show metadata area in root dir', async function() {
+    const dirs = await repo.readDir('/');
+    expect(dirs.map(d => d.fileName)).to.not.include('.zbox-nodejs');
+  });
+
+  it('should read file through snapshot', async function() {
+    let file = await repo.openFile({ path: filePath, opts: { write: true } });
+    await file.writeOnce(new Uint8Array([4, 5]));
+    await file.close();
+
+    const snap = await repo.openSnapshot(snapName);
+    const ents = await snap.readDir(dirPath);
+    expect(ents.map(e => e.path)).to.eql([filePath]);
+
+    const vrdr = await snap.openFile(filePath);
+    const result = await vrdr.readAll();
+    expect(result).to.eql(new Uint8Array([1, 2, 3]));
+    await vrdr.close();
+
+    await expectError(snap.openFile('/non-exist'));
+  });
+
+  it('should restore snapshot', async function() {
+    const extra = `${dirPath}/extra`;
+    let file = await repo.createFile(extra);
+    await file.close();
+
+    const report = await repo.restoreSnapshot(snapName);
+    expect(report.restored).to.be.at.least(1);
+    expect(report.removed).to.be.at.least(1);
+    expect(report.missing).to.be.an('array');
+
+    expect(await repo.pathExists(extra)).to.be.false;
+    file = await repo.openFile(filePath);
+    const result = await file.readAll();
+    expect(result).to.eql(new Uint8Array([1, 2, 3]));
+    await file.close();
+  });
+
+  it('should list changes since a point in time', async function() {
+    const renamed = `${dirPath}/renamed`;
+    const replaced = `${dirPath}/replaced`;
+    let file = await repo.createFile(renamed);
+    await file.close();
+    file = await repo.createFile(replaced);
+    await file.close();
+
+    // times are in seconds, so wait for files above to be older than since
+    await new Promise(resolve => setTimeout(resolve, 1100));
+    const since = new Date();
+    const created = `${dirPath}/created`;
+    const moved = `${dirPath}/moved`;
+    file = await repo.createFile(created);
+    await file.writeOnce(new Uint8Array([1]));
+    await file.close();
+    await repo.rename({ from: created, to: moved });
+    file = await repo.createFile(`${dirPath}/removed`);
+    await file.close();
+    await repo.removeFile(`${dirPath}/removed`);
+    await repo.rename({ from: renamed, to: replaced });
+
+    const changes = await repo.changesSince(since, { path: dirPath });
+    const byPath = {};
+    changes.forEach(chg => { byPath[chg.path] = chg; });
+
+    // created and then renamed in the window is a creation at new path
+    expect(byPath[moved].kind).to.equal('created');
+    expect(byPath[created].kind).to.equal('removed');
+    expect(byPath[`${dirPath}/removed`].kind).to.equal('removed');
+    expect(byPath[replaced].kind).to.equal('renamed');
+    expect(byPath[replaced].renamedFrom).to.equal(renamed);
+    expect(byPath[renamed].kind).to.equal('removed');
+    await expectError(repo.changesSince('not a time'));
+  });
+
+  it('should keep snapshot version after pruning history', async function() {
+    const path = `${dirPath}/pruned`;
+    let file = await repo.createFile(path);
+    await file.writeOnce(new Uint8Array([1]));
+    await file.close();
+    file = await repo.openFile({ path, opts: { write: true } });
+    await file.writeOnce(new Uint8Array([2]));
+    await file.close();
+    await repo.createSnapshot(`${snapName}-prune`);
+    file = await repo.openFile({ path, opts: { write: true } });
+    await file.writeOnce(new Uint8Array([3]));
+    await file.close();
+
+    await repo.pruneHistory(path, { keep: 2 });
+    const snap = await repo.openSnapshot(`${snapName}-prune`);
+    const vrdr = await snap.openFile(path);
+    const result = await vrdr.readAll();
+    expect(result).to.eql(new Uint8Array([2]));
+    await vrdr.close();
+
+    // version recorded in the snapshot is kept
+    const report = await repo.pruneHistory(path, { keep: 1 });
+    expect(report.versionsRemoved).to.equal(0);
+    expect(await repo.history(path)).to.have.length(2);
+  });
+
+  it('should not open non-exist snapshot', async function() {
+    await expectError(repo.openSnapshot('non-exist'));
+    await expectError(repo.restoreSnapshot('non-exist'));
+  });
+
+  after(async function() {
+    if (repo) await repo.close();
+    if (zbox) await zbox.exit();
+  });
+});
+
+// ============================================
+// Host Transfer Test
+// ============================================
+describe('Host Transfer Test', function() {
+  let repo, fs, os, hostDir, repoDir;
+
+  this.timeout(TIMEOUT);
+
+  before(async function() {
+    if (!isNodeJs) this.skip();
+    fs = require('fs');
+    os = require('os');
+    const path = require('path');
+    hostDir = fs.mkdtempSync(path.join(os.tmpdir(), 'zbox-'));
+    fs.mkdirSync(`${hostDir}/sub`);
+    fs.mkdirSync(`${hostDir}/skip`);
+    fs.writeFileSync(`${hostDir}/a.txt`, 'hello');
+    fs.writeFileSync(`${hostDir}/sub/b.bin`, Buffer.alloc(200 * 1024, 7));
+    fs.writeFileSync(`${hostDir}/skip/c.txt`, 'skipped');
+    fs.symlinkSync(`${hostDir}/a.txt`, `${hostDir}/link`);
+    repoDir = `/host-${Date.now()}`;
+    await zbox.initEnv({ log: { level: 'debug' } });
+    repo = await zbox.openRepo({ uri, pwd, opts: { create: true }});
+  });
+
+  it('should import host dir tree', async function() {
+    const events = [];
+    const report = await repo.importFrom(hostDir, repoDir, {
+      filter: path => !path.endsWith('/skip'),
+      onProgress: p => events.push(p)
+    });
+    expect(report.files).to.equal(2);
+    expect(report.dirs).to.equal(1);
+    expect(report.bytes).to.equal(5 + 200 * 1024);
+    expect(report.skipped.map(s => s.reason)).to.eql(['symbolic link']);
+    expect(events.length).to.equal(3);
+    expect(await repo.pathExists(`${repoDir}/skip`)).to.be.false;
+
+    const file = await repo.openFile(`${repoDir}/sub/b.bin`);
+    const content = await file.readAll();
+    expect(content.length).to.equal(200 * 1024);
+    expect(content[100]).to.equal(7);
+    await file.close();
+  });
+
+  it('should not import over existing files', async function() {
+    await expectError(repo.importFrom(hostDir, repoDir));
+    const report = await repo.importFrom(hostDir, repoDir, { overwrite: true });
+    expect(report.files).to.equal(3);
+    await expectError(repo.importFrom(`${hostDir}/non-exist`, repoDir));
+  });
+
+  it('should export repo subtree to host', async function() {
+    const outDir = `${hostDir}/out`;
+    const report = await repo.exportTo(repoDir, outDir);
+    expect(report.files).to.equal(3);
+    expect(report.dirs).to.equal(2);
+    expect(fs.readFileSync(`${outDir}/a.txt`, 'utf8')).to.equal('hello');
+    expect(fs.statSync(`${outDir}/sub/b.bin`).size).to.equal(200 * 1024);
+
+    const md = await repo.metadata(`${repoDir}/a.txt`);
+    const mtime = fs.statSync(`${outDir}/a.txt`).mtimeMs / 1000;
+    expect(Math.floor(mtime)).to.equal(md.modifiedAt);
+
+    await expectError(repo.exportTo(repoDir, outDir));
+    await repo.exportTo(repoDir, outDir, { overwrite: true });
+
+    const old = await repo.exportTo(repoDir, `${hostDir}/old`, {
+      version: new Date(0)
+    });
+    expect(old.files).to.equal(0);
+    expect(old.skipped.length).to.equal(3);
+  });
+
+  it('should export and import tar stream', async function() {
+    const longName = 'n'.repeat(120);
+    let file = await repo.createFile(`${repoDir}/sub/${longName}`);
+    await file.writeOnce(new Uint8Array([1, 2, 3]));
+    await file.close();
+
+    const tarPath = `${hostDir}/repo.tar`;
+    const stream = await repo.exportTar(repoDir);
+    await new Promise((resolve, reject) => {
+      stream.pipe(fs.createWriteStream(tarPath))
+        .on('finish', resolve)
+        .on('error', reject);
+    });
+    expect(fs.statSync(tarPath).size % 512).to.equal(0);
+
+    const dest = `${repoDir}-untar`;
+    const report = await repo.importTar(fs.createReadStream(tarPath), dest);
+    expect(report.files).to.equal(4);
+    expect(report.dirs).to.equal(2);
+    expect(report.skipped).to.eql([]);
+
+    file = await repo.openFile(`${dest}/sub/${longName}`);
+    expect(await file.readAll()).to.eql(Buffer.from([1, 2, 3]));
+    await file.close();
+    file = await repo.openFile(`${dest}/sub/b.bin`);
+    expect((await file.readAll()).length).to.equal(200 * 1024);
+    await file.close();
+
+    await expectError(
+      repo.importTar(fs.createReadStream(tarPath), dest)
+    );
+    const { Readable } = require('stream');
+    await expectError(
+      repo.importTar(Readable.from([Buffer.alloc(700, 1)]), `${dest}-bad`)
+    );
+  });
+
+  it('should export and import zip archive', async function() {
+    let zip = await repo.exportZip(repoDir, { compressionLevel: 9 });
+    expect(zip.slice(0, 2).toString()).to.equal('PK');
+
+    let dest = `${repoDir}-unzip`;
+    let report = await repo.importZip(zip, dest);
+    expect(report.files).to.equal(4);
+    expect(report.dirs).to.equal(2);
+    let file = await repo.openFile(`${dest}/a.txt`);
+    expect((await file.readAll()).toString()).to.equal('hello');
+    await file.close();
+    await expectError(repo.importZip(zip, dest));
+
+    // encrypted archive, read back from a stream
+    zip = await repo.exportZip(`${repoDir}/a.txt`, { password: 'secret' });
+    const zipPath = `${hostDir}/a.zip`;
+    fs.writeFileSync(zipPath, zip);
+    dest = `${repoDir}-unzip-aes`;
+    await expectError(repo.importZip(zip, dest, { password: 'wrong' }));
+    report = await repo.importZip(fs.createReadStream(zipPath), dest, {
+      password: 'secret'
+    });
+    expect(report.files).to.equal(1);
+    file = await repo.openFile(`${dest}/a.txt`);
+    expect((await file.readAll()).toString()).to.equal('hello');
+    await file.close();
+
+    await expectError(repo.importZip(Buffer.from('not a zip'), dest));
+  });
+
+  after(async function() {
+    if (hostDir) fs.rmdirSync(hostDir, { recursive: true });
+    if (repo) await repo.close();
+    if (zbox) await zbox.exit();
+  });
+});
+
+// ============================================
+// Repo Migrate Test
+// ============================================
+describe('Repo Migrate Test', function() {
+  let fs, hostDir, srcUri, dstUri;
+
+  this.timeout(TIMEOUT);
+
+  before(async function() {
+    if (!isNodeJs) this.skip();
+    fs = require('fs');
+    const os = require('os');
+    const path = require('path');
+    hostDir = fs.mkdtempSync(path.join(os.tmpdir(), 'zbox-'));
+    srcUri = `file://${hostDir}/src`;
+    dstUri = `file://${hostDir}/dst`;
+    await zbox.initEnv({ log: { level: 'debug' } });
+
+    const repo = await zbox.openRepo({ uri: srcUri, pwd, opts: {
+      create: true,
+      opsLimit: Zbox.OpsLimit.Interactive,
+      memLimit: Zbox.MemLimit.Interactive
+    }});
+    await repo.createDirAll('/a/b');
+    let file = await repo.createFile('/a/b/c.txt');
+    await file.writeOnce('foo');
+    await file.close();
+    file = await repo.openFile({ path: '/a/b/c.txt', opts: { write: true } });
+    await file.writeOnce('foobar');
+    await file.close();
+    await repo.close();
+  });
+
+  it('should migrate repo with current content', async function() {
+    const events = [];
+    const report = await zbox.migrateRepo({
+      fromUri: srcUri,
+      fromPwd: pwd,
+      toUri: dstUri,
+      toPwd: 'newpwd',
+      opts: {
+        compress: true,
+        opsLimit: Zbox.OpsLimit.Interactive,
+        memLimit: Zbox.MemLimit.Interactive
+      },
+      onProgress: p => events.push(p)
+    });
+    expect(report).to.eql({ files: 1, dirs: 2, versions: 1, bytes: 6 });
+    expect(events.map(p => p.path)).to.eql(['/a', '/a/b', '/a/b/c.txt']);
+
+    const repo = await zbox.openRepo({ uri: dstUri, pwd: 'newpwd' });
+    expect((await repo.info()).compress).to.be.true;
+    const file = await repo.openFile('/a/b/c.txt');
+    expect(await file.readAllString()).to.equal('foobar');
+    await file.close();
+    await repo.close();
+
+    // target has to be a new repo
+    await expectError(zbox.migrateRepo({
+      fromUri: srcUri, fromPwd: pwd, toUri: dstUri, toPwd: 'newpwd'
+    }));
+  });
+
+  it('should migrate repo with all versions', async function() {
+    const toUri = `${dstUri}-all`;
+    const report = await zbox.migrateRepo({
+      fromUri: srcUri,
+      fromPwd: pwd,
+      toUri,
+      toPwd: pwd,
+      allVersions: true,
+      opts: {
+        opsLimit: Zbox.OpsLimit.Interactive,
+        memLimit: Zbox.MemLimit.Interactive
+      }
+    });
+    const srcRepo = await zbox.openRepo({ uri: srcUri, pwd });
+    const srcHist = await srcRepo.history('/a/b/c.txt');
+    await srcRepo.close();
+    expect(report.versions).to.equal(srcHist.length);
+    expect(report.bytes).to.equal(srcHist.reduce((n, v) => n + v.contentLen, 0));
+
+    const repo = await zbox.openRepo({ uri: toUri, pwd });
+    const hist = await repo.history('/a/b/c.txt');
+    expect(hist.map(v => v.contentLen)).to.eql(srcHist.map(v => v.contentLen));
+    await repo.close();
+  });
+
+  it('should mirror repo incrementally', async function() {
+    const opts = {
+      create: true,
+      opsLimit: Zbox.OpsLimit.Interactive,
+      memLimit: Zbox.MemLimit.Interactive
+    };
+    const src = await zbox.openRepo({ uri: srcUri, pwd, opts });
+    const dst = await zbox.openRepo({ uri: `${dstUri}-mirror`, pwd, opts });
+
+    let report = await zbox.mirror(src, dst, { dryRun: true });
+    expect(report.added).to.eql(['/a', '/a/b', '/a/b/c.txt']);
+    expect(await dst.pathExists('/a')).to.be.false;
+
+    report = await zbox.mirror(src, dst);
+    expect(report.added.length).to.equal(3);
+    expect(report.bytes).to.equal(6);
+    let file = await dst.openFile('/a/b/c.txt');
+    expect(await file.readAllString()).to.equal('foobar');
+    await file.close();
+
+    // nothing changed since last mirror
+    report = await zbox.mirror(src, dst);
+    expect(report).to.eql({ added: [], modified: [], deleted: [], bytes: 0 });
+
+    file = await src.openFile({
+      path: '/a/b/c.txt',
+      opts: { write: true }
+    });
+    await file.writeOnce('baz');
+    await file.close();
+    await dst.createFile('/a/extra.txt');
+    report = await zbox.mirror(src, dst, { path: '/a' });
+    expect(report.modified).to.eql(['/a/b/c.txt']);
+    expect(report.deleted).to.eql([]);
+    report = await zbox.mirror(src, dst, { deleteExtraneous: true });
+    expect(report.deleted).to.eql(['/a/extra.txt']);
+    expect(await dst.pathExists('/a/extra.txt')).to.be.false;
+
+    await expectError(zbox.mirror(src, src));
+    await expectError(zbox.mirror(src, {}));
+    await src.close();
+    await dst.close();
+  });
+
+  it('should mirror file over directory and back', async function() {
+    const opts = {
+      create: true,
+      opsLimit: Zbox.OpsLimit.Interactive,
+      memLimit: Zbox.MemLimit.Interactive
+    };
+    const src = await zbox.openRepo({ uri: srcUri, pwd, opts });
+    const dst = await zbox.openRepo({ uri: `${dstUri}-swap`, pwd, opts });
+
+    let file = await src.createFile('/swap');
+    await file.writeOnce('file');
+    await file.close();
+    await dst.createDirAll('/swap/sub');
+    let report = await zbox.mirror(src, dst);
+    expect(report.modified).to.eql(['/swap']);
+    expect(await dst.isFile('/swap')).to.be.true;
+
+    await src.removeFile('/swap');
+    await src.createDir('/swap');
+    report = await zbox.mirror(src, dst);
+    expect(report.modified).to.eql(['/swap']);
+    expect(await dst.isDir('/swap')).to.be.true;
+
+    await src.removeDir('/swap');
+    await src.close();
+    await dst.close();
+  });
+
+  it('should diff trees between repos', async function() {
+    const src = await zbox.openRepo({ uri: srcUri, pwd });
+    const dst = await zbox.openRepo({ uri: `${dstUri}-mirror`, pwd });
+
+    let diff = await zbox.diffTrees(src, '/', dst, '/');
+    expect(diff).to.eql({ added: [], removed: [], changed: [] });
+
+    let file = await dst.openFile({
+      path: '/a/b/c.txt',
+      opts: { write: true }
+    });
+    await file.writeOnce('qux');
+    await file.close();
+    await dst.createFile('/a/d.txt');
+    await src.createDir('/e');
+
+    diff = await zbox.diffTrees(src, '/', dst, '/');
+    expect(diff).to.eql({ added: ['a/d.txt'], removed: ['e'], changed: [] });
+    diff = await zbox.diffTrees(src, '/', dst, '/', { compareContent: true });
+    expect(diff.changed).to.eql(['a/b/c.txt']);
+
+    // two paths in the same repo
+    diff = await zbox.diffTrees(src, '/a', src, '/a/b');
+    expect(diff.removed).to.eql(['b', 'b/c.txt']);
+    expect(diff.added).to.eql(['c.txt']);
+
+    await expectError(zbox.diffTrees(src, '/non-exist', dst, '/'));
+    await src.close();
+    await dst.close();
+  });
+
+  it('should open repo with binary password and key file', async function() {
+    const opts = {
+      create: true,
+      opsLimit: Zbox.OpsLimit.Interactive,
+      memLimit: Zbox.MemLimit.Interactive
+    };
+    const binUri = `file://${hostDir}/bin-pwd`;
+    const binPwd = Buffer.from([0, 1, 2, 250, 255]);
+    let repo = await zbox.openRepo({ uri: binUri, pwd: binPwd, opts });
+    await repo.close();
+    repo = await zbox.openRepo({
+      uri: binUri,
+      pwd: new Uint8Array([0, 1, 2, 250, 255])
+    });
+    await repo.close();
+    await expectError(zbox.openRepo({ uri: binUri, pwd: Buffer.from([0]) }));
+
+    const keyFile = `${hostDir}/repo.key`;
+    fs.writeFileSync(keyFile, Buffer.alloc(64, 9));
+    const keyUri = `file://${hostDir}/key-file`;
+    repo = await zbox.openRepo({ uri: keyUri, opts: { ...opts, keyFile } });
+    await repo.close();
+    repo = await zbox.openRepo({ uri: keyUri, opts: { keyFile } });
+    await repo.resetPassword({
+      oldKeyFile: keyFile,
+      newPwd: binPwd,
+      opsLimit: Zbox.OpsLimit.Interactive,
+      memLimit: Zbox.MemLimit.Interactive
+    });
+    await repo.close();
+    await expectError(zbox.openRepo({ uri: keyUri, opts: { keyFile } }));
+    repo = await zbox.openRepo({ uri: keyUri, pwd: binPwd });
+    await repo.close();
+
+    await expectError(zbox.openRepo({ uri: keyUri, pwd, opts: { keyFile } }));
+    await expectError(zbox.openRepo({
+      uri: keyUri,
+      opts: { keyFile: `${hostDir}/non-exist.key` }
+    }));
+  });
+
+  it('should take binary password and key file everywhere', async function() {
+    const binUri = `file://${hostDir}/bin-pwd`;
+    const binPwd = Buffer.from([0, 1, 2, 250, 255]);
+    expect(await zbox.verifyPassword({ uri: binUri, pwd: binPwd })).to.be.true;
+    expect(await zbox.verifyPassword({
+      uri: binUri,
+      pwd: Buffer.from([0])
+    })).to.be.false;
+    const check = await zbox.checkSuperBlock({ uri: binUri, pwd: binPwd });
+    expect(check.consistent).to.be.true;
+    await zbox.repairSuperBlock({ uri: binUri, pwd: binPwd });
+
+    const keyFile = `${hostDir}/bin.key`;
+    fs.writeFileSync(keyFile, Buffer.alloc(32, 7));
+    const report = await zbox.migrateRepo({
+      fromUri: binUri,
+      fromPwd: binPwd,
+      toUri: `${dstUri}-key-file`,
+      toKeyFile: keyFile,
+      opts: {
+        opsLimit: Zbox.OpsLimit.Interactive,
+        memLimit: Zbox.MemLimit.Interactive
+      }
+    });
+    expect(report.files).to.equal(0);
+    expect(await zbox.verifyPassword({
+      uri: `${dstUri}-key-file`,
+      keyFile
+    })).to.be.true;
+
+    await expectError(zbox.verifyPassword({ uri: binUri }));
+    await expectError(zbox.verifyPassword({ uri: binUri, pwd, keyFile }));
+  });
+
+  it('should wipe buffer password after use on request', async function() {
+    const binUri = `file://${hostDir}/bin-pwd`;
+    let binPwd = Buffer.from([0, 1, 2, 250, 255]);
+    let repo = await zbox.openRepo({ uri: binUri, pwd: binPwd });
+    await repo.close();
+    expect(binPwd).to.eql(Buffer.from([0, 1, 2, 250, 255]));
+
+    repo = await zbox.openRepo({
+      uri: binUri,
+      pwd: binPwd,
+      opts: { wipePassword: true }
+    });
+    expect(binPwd).to.eql(Buffer.alloc(5));
+
+    // wiped even if the password is rejected
+    binPwd = new Uint8Array([1, 2, 3]);
+    await expectError(zbox.openRepo({
+      uri: binUri,
+      pwd: binPwd,
+      opts: { wipePassword: true }
+    }));
+    expect(binPwd).to.eql(new Uint8Array(3));
+
+    const oldPwd = Buffer.from([0, 1, 2, 250, 255]);
+    const newPwd = Buffer.from([9, 9, 9]);
+    await repo.resetPassword({
+      oldPwd,
+      newPwd,
+      opsLimit: Zbox.OpsLimit.Interactive,
+      memLimit: Zbox.MemLimit.Interactive,
+      wipePassword: true
+    });
+    expect(oldPwd).to.eql(Buffer.alloc(5));
+    expect(newPwd).to.eql(Buffer.alloc(3));
+    await repo.close();
+    repo = await zbox.openRepo({ uri: binUri, pwd: Buffer.from([9, 9, 9]) });
+
+    // key file on one side, buffer on the other
+    const keyFile = `${hostDir}/wipe.key`;
+    fs.writeFileSync(keyFile, Buffer.alloc(32, 5));
+    let bufPwd = Buffer.from([9, 9, 9]);
+    await repo.resetPassword({
+      oldPwd: bufPwd,
+      newKeyFile: keyFile,
+      opsLimit: Zbox.OpsLimit.Interactive,
+      memLimit: Zbox.MemLimit.Interactive,
+      wipePassword: true
+    });
+    expect(bufPwd).to.eql(Buffer.alloc(3));
+    bufPwd = Buffer.from([7, 7]);
+    await repo.resetPassword({
+      oldKeyFile: keyFile,
+      newPwd: bufPwd,
+      opsLimit: Zbox.OpsLimit.Interactive,
+      memLimit: Zbox.MemLimit.Interactive,
+      wipePassword: true
+    });
+    expect(bufPwd).to.eql(Buffer.alloc(2));
+    await repo.close();
+
+    bufPwd = Buffer.from([7, 7]);
+    expect(await zbox.verifyPassword({
+      uri: binUri,
+      pwd: bufPwd,
+      wipePassword: true
+    })).to.be.true;
+    expect(bufPwd).to.eql(Buffer.alloc(2));
+  });
+
+  it('should remove new target when migration fails', async function() {
+    const args = {
+      fromUri: srcUri,
+      fromPwd: pwd,
+      toUri: `${dstUri}-retry`,
+      toPwd: pwd,
+      opts: {
+        opsLimit: Zbox.OpsLimit.Interactive,
+        memLimit: Zbox.MemLimit.Interactive
+      }
+    };
+    await expectError(zbox.migrateRepo({
+      ...args,
+      onProgress: () => { throw new Error('stop'); }
+    }));
+    expect(await zbox.exists(args.toUri)).to.be.false;
+    const report = await zbox.migrateRepo(args);
+    expect(report.files).to.equal(1);
+  });
+
+  it('should not migrate with wrong arguments', async function() {
+    await expectError(zbox.migrateRepo({ fromUri: srcUri, fromPwd: pwd }));
+    await expectError(zbox.migrateRepo({
+      fromUri: srcUri,
+      fromPwd: 'wrong pwd',
+      toUri: `${dstUri}-wrong`,
+      toPwd: pwd
+    }));
+    await expectError(zbox.migrateRepo({
+      fromUri: srcUri,
+      fromPwd: pwd,
+      toUri: `${dstUri}-wrong`,
+      toPwd: pwd,
+      onProgress: 'not a function'
+    }));
+  });
+
+  after(async function() {
+    if (hostDir) fs.rmdirSync(hostDir, { recursive: true });
+  });
+});
+
 // ============================================
 // FS Test
 // ============================================
@@ -699,6 +1775,63 @@ describe('FS Test', function() {
     expect(hist[0].createdAt).to.be.a('number');
   });
 
+  it('should prune history of a file', async function() {
+    const path = `/${Date.now()}.prune`;
+    let file = await repo.createFile(path);
+    await file.writeOnce(new Uint8Array([1]));
+    await file.close();
+
+    // reopen so each write replaces content from the start
+    file = await repo.openFile({ path, opts: { write: true } });
+    await file.writeOnce(new Uint8Array([2, 3]));
+    await file.close();
+    file = await repo.openFile({ path, opts: { write: true } });
+    await file.writeOnce(new Uint8Array([4, 5, 6]));
+    await file.close();
+
+    await expectError(repo.pruneHistory(path, {}));
+    await expectError(repo.pruneHistory(path, { keep: 'all' }));
+
+    const report = await repo.pruneHistory(path, { keep: 2 });
+    expect(report.filesPruned).to.equal(1);
+    expect(report.versionsRemoved).to.equal(2);
+    expect(report.bytesRemoved).to.equal(1);
+    expect(report.renumbered).to.eql({ [path]: { 3: 1, 4: 2 } });
+
+    const hist = await repo.history(path);
+    expect(hist.length).to.equal(2);
+    expect(hist[0].contentLen).to.equal(2);
+    expect(hist[1].contentLen).to.equal(3);
+
+    file = await repo.openFile(path);
+    const result = await file.readAll();
+    expect(result).to.eql(new Uint8Array([4, 5, 6]));
+    await file.close();
+  });
+
+  it('should keep file options when pruning history', async function() {
+    const path = `/${Date.now()}.limited`;
+    const write = async (buf, opts = {}) => {
+      const file = await repo.openFile({ path, opts: { write: true, ...opts } });
+      await file.writeOnce(new Uint8Array(buf));
+      await file.close();
+    };
+    await write([1], { create: true, versionLimit: 3 });
+    await write([2]);
+    await repo.pruneHistory(path, { keep: 1 });
+    expect(await repo.history(path)).to.have.length(1);
+
+    for (let i = 0; i < 4; i++) await write([i]);
+    expect(await repo.history(path)).to.have.length(3);
+    await repo.removeFile(path);
+  });
+
+  it('should prune history of all files', async function() {
+    const report = await repo.pruneAllHistory({ olderThan: 0 });
+    expect(report.versionsRemoved).to.equal(0);
+    expect(report.bytesRemoved).to.equal(0);
+  });
+
   it('should copy a file', async function() {
     const to = filePath + '.copy';
     await repo.copy({ from: filePath, to });
@@ -730,6 +1863,96 @@ describe('FS Test', function() {
     await expectError(repo.rename({ from: filePath, to }));
   });
 
+  it('should copy a dir tree', async function() {
+    const from = `/tree-${Date.now()}`;
+    const to = `${from}.copy`;
+    await repo.createDirAll(`${from}/sub`);
+    let file = await repo.createFile(`${from}/sub/file`);
+    await file.writeOnce(new Uint8Array([1]));
+    await file.close();
+    file = await repo.openFile({ path: `${from}/sub/file`, opts: { write: true } });
+    await file.writeOnce(new Uint8Array([1, 2]));
+    await file.close();
+
+    const events = [];
+    let report = await repo.copyDirAll(from, to, {
+      onProgress: p => events.push(p)
+    });
+    expect(report.copied).to.equal(2);
+    expect(events.map(e => e.done)).to.eql([1, 2]);
+    expect(events[1]).to.eql({ done: 2, total: 2, path: `${to}/sub/file` });
+
+    // existing files are kept unless overwrite is set
+    try {
+      await repo.copyDirAll(from, to);
+      expect.fail('copy should fail');
+    } catch (err) {
+      expect(err.path).to.equal(`${to}/sub/file`);
+      expect(err.done).to.equal(1);
+    }
+
+    report = await repo.copyDirAll(from, `${to}2`, { preserveHistory: true });
+    expect(report.copied).to.equal(2);
+    const hist = await repo.history(`${to}2/sub/file`);
+    const srcHist = await repo.history(`${from}/sub/file`);
+    expect(hist.length).to.equal(srcHist.length);
+    report = await repo.copyDirAll(from, `${to}2`, {
+      overwrite: true,
+      preserveHistory: true
+    });
+    expect(report.copied).to.equal(2);
+    expect(await repo.history(`${to}2/sub/file`)).to.have.length(hist.length);
+    const names = (await repo.readDir(`${to}2/sub`)).map(e => e.fileName);
+    expect(names).to.eql(['file']);
+
+    await expectError(repo.copyDirAll(from, `${from}/sub/inner`));
+    await repo.removeDirAll(from);
+    await repo.removeDirAll(to);
+    await repo.removeDirAll(`${to}2`);
+  });
+
+  it('should move a dir tree', async function() {
+    const from = `/move-${Date.now()}`;
+    const to = `${from}.moved`;
+    await repo.createDirAll(`${from}/a/b`);
+    await repo.createDirAll(`${to}/old`);
+
+    await expectError(repo.move(from, to));
+    expect(await repo.isDir(`${to}/old`)).to.be.true;
+    const report = await repo.move(from, to, { overwrite: true });
+    expect(report.moved).to.equal(3);
+    expect(await repo.isDir(`${to}/a/b`)).to.be.true;
+    expect(await repo.pathExists(`${to}/old`)).to.be.false;
+    expect(await repo.pathExists(from)).to.be.false;
+    const names = (await repo.readDir('/')).map(e => e.fileName);
+    expect(names.filter(name => name.startsWith('.~'))).to.be.empty;
+
+    await expectError(repo.move('/non-exist', from));
+    await repo.removeDirAll(to);
+  });
+
+  it('should not block repo calls from progress callback', async function() {
+    const from = `/reenter-${Date.now()}`;
+    const to = `${from}.copy`;
+    await repo.createDirAll(`${from}/sub`);
+
+    const calls = [];
+    const report = await repo.copyDirAll(from, to, {
+      onProgress: () => {
+        calls.push(repo.pathExists(from));
+        calls.push(repo.close());
+      }
+    });
+    expect(report.copied).to.equal(1);
+    expect(calls).to.have.length(2);
+    for (const call of calls) await expectError(call);
+
+    // repo is still usable after the operation
+    expect(await repo.isDir(`${to}/sub`)).to.be.true;
+    await repo.removeDirAll(from);
+    await repo.removeDirAll(to);
+  });
+
   it('should repair super block', async function() {
     await repo.close();
     await zbox.repairSuperBlock({ uri, pwd });